@@ -1,35 +1,303 @@
 extern crate simd;
 extern crate touptek;
+extern crate png;
 
+use std::cell::{Cell, RefCell};
+use std::collections::VecDeque;
+use std::cmp;
+use std::fs;
+use std::mem;
+use std::path::{Path, PathBuf};
 use std::rc::Rc;
+use std::sync::{Arc, Mutex};
 use std::sync::mpsc::{channel, Sender, Receiver, Select};
 use std::thread;
+use std::time::{Duration, Instant};
 
 use property::Property;
 
 pub enum Event {
     Hotplug(Vec<touptek::Instance>),
     Connect,
-    Image(touptek::Image),
-    StillImage(touptek::Image),
+    /// The camera came back after `Reconnecting`. It starts out with the defaults of
+    /// the SDK, so the settings have to be sent again with `Camera::resend_settings`.
+    Reconnected,
+    PreviewSizes(Vec<touptek::Resolution>),
+    ExposureRange { min_us: u32, max_us: u32 },
+    /// What the connected camera can do; sent once after connecting.
+    Capabilities(Capabilities),
+    /// A new frame can be retrieved with `Camera::take_frame`.
+    Image,
+    StillImage { id: u64, image: touptek::Image },
+    WhiteBalance { temperature: u32, tint: u32 },
+    Exposure { time_us: u32, gain_pct: u16 },
+    Telemetry { temperature_c: f32, frame_count: u64 },
+    /// Something went wrong that the user should know about; the camera thread goes on.
+    Error(String),
+    /// The camera went away on its own, and connecting to it again is being attempted.
+    Reconnecting { attempt: u32 },
+    FrameRate(f32),
     Disconnect,
 }
 
+/// Features that only some cameras have.
+///
+/// The SDK has no queries for these; they are derived from the flags of the camera's
+/// model, as reported by enumeration. It cannot tell whether a camera has status LEDs
+/// at all, so switching one on a camera without is left to fail in the SDK.
+///
+/// Flips and white balance are not listed: every model can flip the image, and only
+/// monochrome ones lack white balance. The exposure range is sent separately, with
+/// `Event::ExposureRange`.
+#[derive(Clone, PartialEq, Debug)]
+pub struct Capabilities {
+    /// A monochrome sensor has no use for white balance, saturation or hue.
+    pub monochrome: bool,
+    pub binning: bool,
+    /// Whether the camera can wait for a trigger, rather than only run freely.
+    pub trigger: bool,
+}
+
+// Model flags of the SDK (`TOUPCAM_FLAG_*` in toupcam.h) that capabilities depend on.
+const FLAG_MONO: u64 = 0x00000010;
+const FLAG_BINSKIP_SUPPORTED: u64 = 0x00000020;
+const FLAG_TRIGGER_SOFTWARE: u64 = 0x00080000;
+const FLAG_TRIGGER_EXTERNAL: u64 = 0x00100000;
+
+impl Capabilities {
+    fn from_model_flags(flags: u64) -> Capabilities {
+        Capabilities {
+            monochrome: flags & FLAG_MONO != 0,
+            binning: flags & FLAG_BINSKIP_SUPPORTED != 0,
+            trigger: flags & (FLAG_TRIGGER_SOFTWARE | FLAG_TRIGGER_EXTERNAL) != 0,
+        }
+    }
+
+    // Looks up the model of the camera that `Toupcam::open(camera_id)` opens, i.e. the first
+    // one found if there is no id; a camera that vanished meanwhile is assumed to do nothing.
+    fn probe(camera_id: Option<&str>) -> Capabilities {
+        let instances = touptek::Toupcam::enumerate();
+        let instance = instances.iter().find(|instance|
+            camera_id.map_or(true, |camera_id| instance.unique_id == camera_id));
+        Capabilities::from_model_flags(instance.map_or(0, |instance| instance.model.flags as u64))
+    }
+}
+
+/// Region of the sensor, in pixels of the current preview size.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Roi {
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+}
+
+// The SDK only accepts a region with an even offset and size, at least 16 pixels across.
+const ROI_ALIGNMENT: u32 = 2;
+const ROI_MIN_SIZE: u32 = 16;
+
+impl Roi {
+    // Grows the region to what the SDK accepts, keeping it within `width` and `height`.
+    fn aligned(&self, width: u32, height: u32) -> Roi {
+        // Returns the new start and length of a span of the region.
+        fn align(start: u32, length: u32, limit: u32) -> (u32, u32) {
+            let limit = limit / ROI_ALIGNMENT * ROI_ALIGNMENT;
+            let end = (start + length + ROI_ALIGNMENT - 1) / ROI_ALIGNMENT * ROI_ALIGNMENT;
+            let end = cmp::min(end, limit);
+            let start = cmp::min(start / ROI_ALIGNMENT * ROI_ALIGNMENT, end);
+            if end - start >= ROI_MIN_SIZE { return (start, end - start) }
+
+            let end = cmp::min(start + ROI_MIN_SIZE, limit);
+            let start = end.saturating_sub(ROI_MIN_SIZE);
+            (start, end - start)
+        }
+
+        let (x, width) = align(self.x, self.width, width);
+        let (y, height) = align(self.y, self.height, height);
+        Roi { x: x, y: y, width: width, height: height }
+    }
+}
+
+/// Format in which the camera delivers pixels. Frames are always converted to RGBA
+/// with 8 bits per channel before they are handed to the UI.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum PixelFormat {
+    Color,
+    /// Less data per frame, for higher frame rates or monochrome sensors.
+    Grayscale,
+}
+
+impl PixelFormat {
+    pub fn all() -> &'static [PixelFormat] {
+        const ALL: &'static [PixelFormat] = &[PixelFormat::Color, PixelFormat::Grayscale];
+        ALL
+    }
+
+    pub fn name(&self) -> &'static str {
+        match *self {
+            PixelFormat::Color => "Color",
+            PixelFormat::Grayscale => "Grayscale",
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum TriggerMode {
+    /// The camera delivers frames continuously.
+    FreeRun,
+    /// The camera delivers a frame whenever `Camera::trigger` is called.
+    Software,
+    /// The camera delivers a frame whenever its trigger input is pulsed.
+    External,
+}
+
+impl TriggerMode {
+    pub fn all() -> &'static [TriggerMode] {
+        const ALL: &'static [TriggerMode] =
+            &[TriggerMode::FreeRun, TriggerMode::Software, TriggerMode::External];
+        ALL
+    }
+
+    pub fn name(&self) -> &'static str {
+        match *self {
+            TriggerMode::FreeRun => "Free run",
+            TriggerMode::Software => "Software",
+            TriggerMode::External => "External",
+        }
+    }
+}
+
+/// Largest binning factor offered; touptek cameras support up to 4x4 binning.
+pub const MAX_BINNING: u32 = 4;
+
+// Settings that a camera starts out with, before the config is applied to it.
+pub const DEFAULT_EXPOSURE_TIME_US: u32 = 120000;
+pub const DEFAULT_EXPOSURE_GAIN_PCT: u16 = 100;
+pub const DEFAULT_COLOR_TEMPERATURE_K: u32 = 6503;
+pub const DEFAULT_TINT: u32 = 1000;
+pub const DEFAULT_GAMMA: u32 = 100;
+pub const DEFAULT_CONTRAST: i32 = 0;
+pub const DEFAULT_SATURATION: u32 = 128;
+pub const DEFAULT_HUE: i32 = 0;
+pub const DEFAULT_SHARPNESS: u32 = 0;
+
+#[derive(PartialEq, Debug)]
 enum Command {
     Connect(Option<String>),
+    Disconnect,
     SetExposureTime { microseconds: u32 },
     SetExposureGain { percents: u16 },
     SetColorTemperature { kelvin: u32 },
     SetTint(u32),
-    Snap,
+    SetGamma(u32),
+    SetContrast(i32),
+    SetSaturation(u32),
+    SetHue(i32),
+    SetSharpness(u32),
+    SetAutoExposure(bool),
+    SetHFlip(bool),
+    SetVFlip(bool),
+    SetRoi(Option<Roi>),
+    SetPreviewSize(usize),
+    SetBitDepth { bits: u8 },
+    SetBinning { factor: u32 },
+    SetPixelFormat(PixelFormat),
+    SetTriggerMode(TriggerMode),
+    SetLed { index: u32, on: bool },
+    SetStreaming(bool),
+    SetReconnectMaxDelay { seconds: u32 },
+    AutoWhiteBalance,
+    AutoExposureOnce,
+    Trigger,
+    Snap(u64),
+}
+
+impl Command {
+    fn supersedes(&self, other: &Command) -> bool {
+        match (self, other) {
+            (&Command::SetExposureTime { .. }, &Command::SetExposureTime { .. }) |
+            (&Command::SetExposureGain { .. }, &Command::SetExposureGain { .. }) |
+            (&Command::SetColorTemperature { .. }, &Command::SetColorTemperature { .. }) |
+            (&Command::SetTint(_), &Command::SetTint(_)) |
+            (&Command::SetGamma(_), &Command::SetGamma(_)) |
+            (&Command::SetContrast(_), &Command::SetContrast(_)) |
+            (&Command::SetSaturation(_), &Command::SetSaturation(_)) |
+            (&Command::SetHue(_), &Command::SetHue(_)) |
+            (&Command::SetSharpness(_), &Command::SetSharpness(_)) |
+            (&Command::SetAutoExposure(_), &Command::SetAutoExposure(_)) |
+            (&Command::SetHFlip(_), &Command::SetHFlip(_)) |
+            (&Command::SetVFlip(_), &Command::SetVFlip(_)) |
+            (&Command::SetRoi(_), &Command::SetRoi(_)) |
+            (&Command::SetPreviewSize(_), &Command::SetPreviewSize(_)) |
+            (&Command::SetBitDepth { .. }, &Command::SetBitDepth { .. }) |
+            (&Command::SetBinning { .. }, &Command::SetBinning { .. }) |
+            (&Command::SetPixelFormat(_), &Command::SetPixelFormat(_)) |
+            (&Command::SetTriggerMode(_), &Command::SetTriggerMode(_)) |
+            (&Command::SetStreaming(_), &Command::SetStreaming(_)) |
+            (&Command::SetReconnectMaxDelay { .. },
+             &Command::SetReconnectMaxDelay { .. }) => true,
+            (&Command::SetLed { index, .. }, &Command::SetLed { index: other_index, .. })
+                if index == other_index => true,
+            _ => false
+        }
+    }
+
+    // Commands that act on the camera as it is set up at that moment, so that settings
+    // must not be moved across them.
+    fn is_barrier(&self) -> bool {
+        match *self {
+            Command::Connect(_) | Command::Disconnect | Command::AutoWhiteBalance |
+            Command::AutoExposureOnce | Command::Trigger | Command::Snap(_) => true,
+            _ => false
+        }
+    }
+}
+
+fn coalesce(commands: Vec<Command>) -> Vec<Command> {
+    let mut coalesced: Vec<Command> = Vec::new();
+    // Commands up to the last barrier are kept as they are.
+    let mut kept = 0;
+    for command in commands {
+        if command.is_barrier() {
+            coalesced.push(command);
+            kept = coalesced.len();
+            continue
+        }
+
+        if let Some(index) = coalesced[kept..].iter().position(|other| command.supersedes(other)) {
+            coalesced.remove(kept + index);
+        }
+        coalesced.push(command)
+    }
+    coalesced
 }
 
 pub struct Camera {
     cmd_tx: Sender<Command>,
+    // Only the latest frame is kept, so that frames do not pile up in memory, each
+    // a full RGBA buffer, if the UI falls behind the camera.
+    latest_frame: Arc<Mutex<Option<touptek::Image>>>,
     exposure_time_us: Rc<Property<u32>>,
     exposure_gain_pct: Rc<Property<u16>>,
     color_temperature_k: Rc<Property<u32>>,
     tint: Rc<Property<u32>>,
+    gamma: Rc<Property<u32>>,
+    contrast: Rc<Property<i32>>,
+    saturation: Rc<Property<u32>>,
+    hue: Rc<Property<i32>>,
+    sharpness: Rc<Property<u32>>,
+    auto_exposure: Rc<Property<bool>>,
+    hflip: Rc<Property<bool>>,
+    vflip: Rc<Property<bool>>,
+    roi: Rc<Property<Option<Roi>>>,
+    preview_size: Rc<Property<usize>>,
+    still_bit_depth: Rc<Property<u8>>,
+    binning: Rc<Property<u32>>,
+    pixel_format: Rc<Property<PixelFormat>>,
+    trigger_mode: Rc<Property<TriggerMode>>,
+    led: Rc<Property<bool>>,
+    streaming: Rc<Property<bool>>,
+    next_snap_id: Cell<u64>,
 }
 
 impl Camera {
@@ -37,36 +305,132 @@ impl Camera {
         let (event_tx, event_rx) = channel();
         let (cmd_tx, cmd_rx) = channel();
 
-        let exposure_time_us = Property::new(120000);
+        let exposure_time_us = Property::new(DEFAULT_EXPOSURE_TIME_US);
         exposure_time_us.notify(&cmd_tx, |value|
             Command::SetExposureTime { microseconds: *value });
 
-        let exposure_gain_pct = Property::new(100);
+        let exposure_gain_pct = Property::new(DEFAULT_EXPOSURE_GAIN_PCT);
         exposure_gain_pct.notify(&cmd_tx, |value|
             Command::SetExposureGain { percents: *value });
 
-        let color_temperature_k = Property::new(6503);
+        let color_temperature_k = Property::new(DEFAULT_COLOR_TEMPERATURE_K);
         color_temperature_k.notify(&cmd_tx, |value|
             Command::SetColorTemperature { kelvin: *value });
 
-        let tint = Property::new(1000);
+        let tint = Property::new(DEFAULT_TINT);
         tint.notify(&cmd_tx, |value|
             Command::SetTint(*value));
 
-        thread::spawn(move || camera_thread(event_tx, cmd_rx));
+        let gamma = Property::new(DEFAULT_GAMMA);
+        gamma.notify(&cmd_tx, |value|
+            Command::SetGamma(*value));
+
+        let contrast = Property::new(DEFAULT_CONTRAST);
+        contrast.notify(&cmd_tx, |value|
+            Command::SetContrast(*value));
+
+        let saturation = Property::new(DEFAULT_SATURATION);
+        saturation.notify(&cmd_tx, |value|
+            Command::SetSaturation(*value));
+
+        let hue = Property::new(DEFAULT_HUE);
+        hue.notify(&cmd_tx, |value|
+            Command::SetHue(*value));
+
+        let sharpness = Property::new(DEFAULT_SHARPNESS);
+        sharpness.notify(&cmd_tx, |value|
+            Command::SetSharpness(*value));
+
+        let auto_exposure = Property::new(false);
+        auto_exposure.notify(&cmd_tx, |value|
+            Command::SetAutoExposure(*value));
+
+        let hflip = Property::new(false);
+        hflip.notify(&cmd_tx, |value|
+            Command::SetHFlip(*value));
+
+        let vflip = Property::new(false);
+        vflip.notify(&cmd_tx, |value|
+            Command::SetVFlip(*value));
+
+        let roi = Property::new(None);
+        roi.notify(&cmd_tx, |value|
+            Command::SetRoi(*value));
+
+        let preview_size = Property::new(0); // largest
+        preview_size.notify(&cmd_tx, |value|
+            Command::SetPreviewSize(*value));
+
+        let still_bit_depth = Property::with_validator(8, |value|
+            if *value > 8 { *value = 16 } else { *value = 8 });
+        still_bit_depth.notify(&cmd_tx, |value|
+            Command::SetBitDepth { bits: *value });
+
+        let binning = Property::with_validator(1, |value|
+            *value = cmp::max(1, cmp::min(*value, MAX_BINNING)));
+        binning.notify(&cmd_tx, |value|
+            Command::SetBinning { factor: *value });
+
+        let pixel_format = Property::new(PixelFormat::Color);
+        pixel_format.notify(&cmd_tx, |value|
+            Command::SetPixelFormat(*value));
+
+        let trigger_mode = Property::new(TriggerMode::FreeRun);
+        trigger_mode.notify(&cmd_tx, |value|
+            Command::SetTriggerMode(*value));
+
+        let led = Property::new(false);
+        led.notify(&cmd_tx, |value|
+            Command::SetLed { index: 0, on: *value });
+
+        let streaming = Property::new(true);
+        streaming.notify(&cmd_tx, |value|
+            Command::SetStreaming(*value));
+
+        let latest_frame = Arc::new(Mutex::new(None));
+        {
+            let latest_frame = latest_frame.clone();
+            thread::spawn(move || camera_thread(event_tx, cmd_rx, latest_frame));
+        }
 
         let camera = Camera {
             cmd_tx: cmd_tx,
+            latest_frame: latest_frame,
             exposure_time_us: exposure_time_us,
             exposure_gain_pct: exposure_gain_pct,
             color_temperature_k: color_temperature_k,
             tint: tint,
+            gamma: gamma,
+            contrast: contrast,
+            saturation: saturation,
+            hue: hue,
+            sharpness: sharpness,
+            auto_exposure: auto_exposure,
+            hflip: hflip,
+            vflip: vflip,
+            roi: roi,
+            preview_size: preview_size,
+            still_bit_depth: still_bit_depth,
+            binning: binning,
+            pixel_format: pixel_format,
+            trigger_mode: trigger_mode,
+            led: led,
+            streaming: streaming,
+            next_snap_id: Cell::new(1),
         };
         (camera, event_rx)
     }
 
+    /// Connects to the camera with the given id, or the first one available; if a camera
+    /// is already connected, it is disconnected first.
     pub fn connect(&self, unique_id: Option<String>) {
+        self.cmd_tx.send(Command::Disconnect).unwrap();
         self.cmd_tx.send(Command::Connect(unique_id)).unwrap();
+        self.resend_settings()
+    }
+
+    /// Sends every setting to the camera once more, e.g. after `Event::Reconnected`.
+    pub fn resend_settings(&self) {
         self.cmd_tx.send(Command::SetExposureTime {
             microseconds: self.exposure_time_us.get() }).unwrap();
         self.cmd_tx.send(Command::SetExposureGain {
@@ -75,6 +439,43 @@ impl Camera {
             kelvin: self.color_temperature_k.get() }).unwrap();
         self.cmd_tx.send(Command::SetTint(
             self.tint.get())).unwrap();
+        self.cmd_tx.send(Command::SetGamma(
+            self.gamma.get())).unwrap();
+        self.cmd_tx.send(Command::SetContrast(
+            self.contrast.get())).unwrap();
+        self.cmd_tx.send(Command::SetSaturation(
+            self.saturation.get())).unwrap();
+        self.cmd_tx.send(Command::SetHue(
+            self.hue.get())).unwrap();
+        self.cmd_tx.send(Command::SetSharpness(
+            self.sharpness.get())).unwrap();
+        self.cmd_tx.send(Command::SetAutoExposure(
+            self.auto_exposure.get())).unwrap();
+        self.cmd_tx.send(Command::SetHFlip(
+            self.hflip.get())).unwrap();
+        self.cmd_tx.send(Command::SetVFlip(
+            self.vflip.get())).unwrap();
+        self.cmd_tx.send(Command::SetRoi(
+            self.roi.get())).unwrap();
+        self.cmd_tx.send(Command::SetPreviewSize(
+            self.preview_size.get())).unwrap();
+        self.cmd_tx.send(Command::SetBitDepth {
+            bits: self.still_bit_depth.get() }).unwrap();
+        self.cmd_tx.send(Command::SetBinning {
+            factor: self.binning.get() }).unwrap();
+        self.cmd_tx.send(Command::SetPixelFormat(
+            self.pixel_format.get())).unwrap();
+        self.cmd_tx.send(Command::SetTriggerMode(
+            self.trigger_mode.get())).unwrap();
+        self.cmd_tx.send(Command::SetLed {
+            index: 0, on: self.led.get() }).unwrap();
+        self.cmd_tx.send(Command::SetStreaming(
+            self.streaming.get())).unwrap();
+    }
+
+    /// Stops the stream and closes the camera, if one is connected.
+    pub fn disconnect(&self) {
+        self.cmd_tx.send(Command::Disconnect).unwrap()
     }
 
     pub fn exposure_time_us(&self) -> Rc<Property<u32>> {
@@ -93,119 +494,570 @@ impl Camera {
         self.tint.clone()
     }
 
-    pub fn snap(&self) {
-        self.cmd_tx.send(Command::Snap).unwrap()
+    pub fn gamma(&self) -> Rc<Property<u32>> {
+        self.gamma.clone()
+    }
+
+    pub fn contrast(&self) -> Rc<Property<i32>> {
+        self.contrast.clone()
+    }
+
+    pub fn saturation(&self) -> Rc<Property<u32>> {
+        self.saturation.clone()
+    }
+
+    pub fn hue(&self) -> Rc<Property<i32>> {
+        self.hue.clone()
+    }
+
+    pub fn sharpness(&self) -> Rc<Property<u32>> {
+        self.sharpness.clone()
+    }
+
+    pub fn auto_exposure(&self) -> Rc<Property<bool>> {
+        self.auto_exposure.clone()
+    }
+
+    pub fn hflip(&self) -> Rc<Property<bool>> {
+        self.hflip.clone()
+    }
+
+    pub fn vflip(&self) -> Rc<Property<bool>> {
+        self.vflip.clone()
+    }
+
+    /// Region of the sensor to capture; `None` captures the entire sensor. The camera
+    /// grows it as needed to the alignment and minimum size that the sensor requires.
+    pub fn roi(&self) -> Rc<Property<Option<Roi>>> {
+        self.roi.clone()
+    }
+
+    pub fn preview_size(&self) -> Rc<Property<usize>> {
+        self.preview_size.clone()
+    }
+
+    pub fn set_preview_size(&self, index: usize) {
+        self.preview_size.set(index)
+    }
+
+    /// Bits per channel of still images; either 8 or 16.
+    pub fn still_bit_depth(&self) -> Rc<Property<u8>> {
+        self.still_bit_depth.clone()
+    }
+
+    /// Sensor pixels combined into one along each axis, from 1 to `MAX_BINNING`.
+    /// Binning trades resolution for sensitivity.
+    pub fn binning(&self) -> Rc<Property<u32>> {
+        self.binning.clone()
+    }
+
+    pub fn pixel_format(&self) -> Rc<Property<PixelFormat>> {
+        self.pixel_format.clone()
+    }
+
+    pub fn trigger_mode(&self) -> Rc<Property<TriggerMode>> {
+        self.trigger_mode.clone()
+    }
+
+    /// Whether the first status LED of the camera is lit; does nothing if the camera
+    /// has none, see `Capabilities`.
+    pub fn led(&self) -> Rc<Property<bool>> {
+        self.led.clone()
+    }
+
+    /// In software trigger mode, makes the camera deliver a single frame, which arrives
+    /// like any other with `Event::Image`; in other modes, does nothing.
+    pub fn trigger(&self) {
+        self.cmd_tx.send(Command::Trigger).unwrap()
+    }
+
+    /// Whether preview frames are delivered; still images can be taken either way.
+    pub fn streaming(&self) -> Rc<Property<bool>> {
+        self.streaming.clone()
+    }
+
+    /// Limits the delay between attempts to reconnect to a camera that went away; the delay
+    /// starts at one second and doubles after every failed attempt.
+    pub fn set_reconnect_max_delay(&self, seconds: u32) {
+        self.cmd_tx.send(Command::SetReconnectMaxDelay { seconds: seconds }).unwrap()
+    }
+
+    /// Adjusts white balance once; the result is reported with `Event::WhiteBalance`.
+    pub fn auto_white_balance(&self) {
+        self.cmd_tx.send(Command::AutoWhiteBalance).unwrap()
+    }
+
+    /// Retrieves the most recent frame; frames that arrived before it are dropped.
+    pub fn take_frame(&self) -> Option<touptek::Image> {
+        self.latest_frame.lock().unwrap().take()
+    }
+
+    /// Lets the camera adjust exposure time and gain until they settle, and then fixes them;
+    /// the result is reported with `Event::Exposure`.
+    pub fn auto_exposure_once(&self) {
+        self.cmd_tx.send(Command::AutoExposureOnce).unwrap()
+    }
+
+    /// Requests a still image; returns the id it will be reported with in `Event::StillImage`.
+    pub fn snap(&self) -> u64 {
+        let id = self.next_snap_id.get();
+        self.next_snap_id.set(id + 1);
+        self.cmd_tx.send(Command::Snap(id)).unwrap();
+        id
     }
 }
 
-fn camera_thread(event_tx: Sender<Event>, cmd_rx: Receiver<Command>) {
-    event_tx.send(Event::Hotplug(touptek::Toupcam::enumerate())).unwrap();
+impl Drop for Camera {
+    fn drop(&mut self) {
+        // The camera thread may have already exited if the process is shutting down.
+        self.cmd_tx.send(Command::Disconnect).unwrap_or(())
+    }
+}
+
+// Sends an event to the UI; if the UI is gone, there is nothing left to do, so this returns
+// from the enclosing closure or function, which makes the camera thread wind down.
+macro_rules! send_or_return {
+    ($tx:expr, $event:expr) => (if $tx.send($event).is_err() { return })
+}
+
+// How often the sensor status is reported while connected.
+const TELEMETRY_INTERVAL_MS: u64 = 1000;
+
+// Longest a one-time auto exposure may keep adjusting, for scenes that never settle.
+const AUTO_EXPOSURE_ONCE_MAX_S: u32 = 10;
+
+/// Longest delay between attempts to reconnect to a camera, unless configured otherwise.
+pub const RECONNECT_MAX_DELAY_S: u32 = 30;
+
+// Reconnection to a camera that went away without being asked to. Delays are counted
+// in ticks, i.e. seconds.
+struct Reconnect {
+    camera_id: Option<String>,
+    attempt: u32,
+    delay: u32,
+    waited: u32,
+}
+
+fn camera_thread(event_tx: Sender<Event>, cmd_rx: Receiver<Command>,
+                 latest_frame: Arc<Mutex<Option<touptek::Image>>>) {
+    let still_bits = Cell::new(8);
+    let pixel_format = Cell::new(PixelFormat::Color);
+    let trigger_mode = Cell::new(TriggerMode::FreeRun);
+    let streaming = Cell::new(true);
+    // While a one-time auto exposure is running, whether the exposure has changed since
+    // the last tick, and how many ticks it has run for; once a whole tick passes without
+    // changes, or it has run for too long, it is considered settled.
+    let auto_exposure_once: Cell<Option<(bool, u32)>> = Cell::new(None);
+    // Whether automatic exposure is on; a one-time auto exposure returns to it once settled.
+    let auto_exposure = Cell::new(false);
+    let frame_count = Cell::new(0u64);
+    let frame_count_at_tick = Cell::new(0u64);
+    // Ticks are late whenever the thread is busy, so the frame rate uses the actual time.
+    let last_tick_at = Cell::new(Instant::now());
+    // The camera completes still images in the order they were requested.
+    let pending_snaps = RefCell::new(VecDeque::new());
+    // Commands that were received after a disconnection was requested, and are yet to be
+    // processed; these are typically the connection to another camera and its settings.
+    let deferred_cmds = RefCell::new(VecDeque::new());
+    // The camera that is connected, as it was asked for.
+    let connected_id = RefCell::new(None);
+    let reconnect: RefCell<Option<Reconnect>> = RefCell::new(None);
+    let reconnect_max_delay = Cell::new(RECONNECT_MAX_DELAY_S);
+
+    let (tick_tx, tick_rx) = channel();
+    thread::spawn(move || {
+        loop {
+            thread::sleep(Duration::from_millis(TELEMETRY_INTERVAL_MS));
+            if tick_tx.send(()).is_err() { break }
+        }
+    });
+
+    send_or_return!(event_tx, Event::Hotplug(touptek::Toupcam::enumerate()));
 
     touptek::Toupcam::hotplug(|hotplug_rx| {
         loop {
-            {
-                let select = Select::new();
-                let mut cmd_rx = select.handle(&cmd_rx);
-                let mut hotplug_rx = select.handle(&hotplug_rx);
+            let command = deferred_cmds.borrow_mut().pop_front();
+            let command = match command {
+                Some(command) => command,
+                None => {
+                    let mut reconnect_now = false;
+                    {
+                        let select = Select::new();
+                        let mut cmd_rx = select.handle(&cmd_rx);
+                        let mut hotplug_rx = select.handle(&hotplug_rx);
+                        let mut tick_rx = select.handle(&tick_rx);
 
-                unsafe {
-                    cmd_rx.add();
-                    hotplug_rx.add();
-                }
+                        unsafe {
+                            cmd_rx.add();
+                            hotplug_rx.add();
+                            tick_rx.add();
+                        }
 
-                loop {
-                    let id = select.wait();
+                        loop {
+                            let id = select.wait();
 
-                    if id == hotplug_rx.id() {
-                        hotplug_rx.recv().unwrap();
-                        event_tx.send(Event::Hotplug(touptek::Toupcam::enumerate())).unwrap()
+                            if id == hotplug_rx.id() {
+                                hotplug_rx.recv().unwrap();
+                                send_or_return!(event_tx, Event::Hotplug(
+                                    touptek::Toupcam::enumerate()))
+                            }
+
+                            if id == tick_rx.id() {
+                                tick_rx.recv().unwrap();
+                                if let Some(ref mut reconnect) = *reconnect.borrow_mut() {
+                                    reconnect.waited += 1;
+                                    reconnect_now = reconnect.waited >= reconnect.delay
+                                }
+                                if reconnect_now { break }
+                            }
+
+                            if id == cmd_rx.id() {
+                                break
+                            }
+                        }
                     }
 
-                    if id == cmd_rx.id() {
-                        break
+                    if reconnect_now {
+                        let mut reconnect = reconnect.borrow_mut();
+                        let reconnect = reconnect.as_mut().unwrap();
+                        reconnect.attempt += 1;
+                        send_or_return!(event_tx, Event::Reconnecting {
+                            attempt: reconnect.attempt });
+                        Command::Connect(reconnect.camera_id.clone())
+                    } else {
+                        let command = match cmd_rx.recv() {
+                            Ok(command) => command,
+                            Err(_) => return // the Camera was dropped
+                        };
+                        match command {
+                            // The user has decided what to connect to instead.
+                            Command::Connect(_) | Command::Disconnect =>
+                                *reconnect.borrow_mut() = None,
+                            _ => ()
+                        }
+                        command
                     }
                 }
-            }
+            };
 
+            let mut reconnected = false;
             let cam =
-                match cmd_rx.recv().unwrap() {
+                match command {
                     Command::Connect(camera_id) => {
                         match touptek::Toupcam::open(camera_id.as_ref().map(|s| &s[..])) {
-                            Some(camera) => camera,
-                            None => continue,
+                            Some(camera) => {
+                                *connected_id.borrow_mut() = camera_id;
+                                reconnected = reconnect.borrow_mut().take().is_some();
+                                camera
+                            }
+                            None => {
+                                if let Some(ref mut reconnect) = *reconnect.borrow_mut() {
+                                    reconnect.delay =
+                                        cmp::min(reconnect.delay * 2, reconnect_max_delay.get());
+                                    reconnect.waited = 0;
+                                    continue
+                                }
+
+                                // Without an id, there was simply no camera to connect to.
+                                if let Some(camera_id) = camera_id {
+                                    send_or_return!(event_tx, Event::Error(
+                                        format!("Cannot open camera {}", camera_id)))
+                                }
+                                continue
+                            }
                         }
                     }
+                    Command::SetReconnectMaxDelay { seconds } => {
+                        reconnect_max_delay.set(cmp::max(seconds, 1));
+                        continue
+                    }
                     _ => continue
                 };
 
+            let resolutions = cam.resolutions();
             cam.set_preview_size_index(0); // largest
-            cam.set_automatic_exposure(false);
 
+            // Ticks keep arriving while no camera is connected; discard them.
+            while let Ok(()) = tick_rx.try_recv() {}
+            frame_count.set(0);
+            frame_count_at_tick.set(0);
+            last_tick_at.set(Instant::now());
+            pending_snaps.borrow_mut().clear();
+            auto_exposure_once.set(None);
+            // A camera that has just been opened runs freely until told otherwise.
+            trigger_mode.set(TriggerMode::FreeRun);
             cam.start(|cam_rx| {
-                event_tx.send(Event::Connect).unwrap();
+                send_or_return!(event_tx,
+                                if reconnected { Event::Reconnected } else { Event::Connect });
+                send_or_return!(event_tx, Event::PreviewSizes(resolutions.clone()));
+                let (min_us, max_us, _default_us) = cam.exposure_time_range();
+                send_or_return!(event_tx, Event::ExposureRange { min_us: min_us, max_us: max_us });
+                let capabilities =
+                    Capabilities::probe(connected_id.borrow().as_ref().map(|s| &s[..]));
+                send_or_return!(event_tx, Event::Capabilities(capabilities.clone()));
+
+                // Applies commands to the camera; returns false if it should be disconnected,
+                // in which case the commands that follow are left for the outer loop.
+                let run_commands = |commands: Vec<Command>| -> bool {
+                    let mut commands = coalesce(commands).into_iter();
+                    while let Some(command) = commands.next() {
+                        match command {
+                            Command::Connect(_) | Command::Disconnect => {
+                                let mut deferred_cmds = deferred_cmds.borrow_mut();
+                                deferred_cmds.push_back(command);
+                                deferred_cmds.extend(commands);
+                                return false
+                            }
+                            Command::SetExposureTime { microseconds } =>
+                                cam.set_exposure_time(microseconds),
+                            Command::SetExposureGain { percents } =>
+                                cam.set_exposure_gain(percents),
+                            Command::SetColorTemperature { kelvin } =>
+                                cam.set_white_balance_temp_tint(
+                                    touptek::WhiteBalanceTempTint {
+                                        temperature: kelvin, ..cam.white_balance_temp_tint() }),
+                            Command::SetTint(tint) =>
+                                cam.set_white_balance_temp_tint(
+                                    touptek::WhiteBalanceTempTint {
+                                        tint: tint, ..cam.white_balance_temp_tint() }),
+                            Command::SetGamma(gamma) =>
+                                cam.set_gamma(gamma),
+                            Command::SetContrast(contrast) =>
+                                cam.set_contrast(contrast),
+                            Command::SetSaturation(saturation) =>
+                                cam.set_saturation(saturation),
+                            Command::SetHue(hue) =>
+                                cam.set_hue(hue),
+                            Command::SetSharpness(sharpness) =>
+                                cam.set_sharpness(sharpness),
+                            Command::SetAutoExposure(enabled) => {
+                                cam.set_automatic_exposure(enabled);
+                                auto_exposure.set(enabled)
+                            }
+                            Command::SetHFlip(enabled) =>
+                                cam.set_hflip(enabled),
+                            Command::SetVFlip(enabled) =>
+                                cam.set_vflip(enabled),
+                            Command::SetRoi(None) =>
+                                cam.set_roi(0, 0, 0, 0), // entire sensor
+                            Command::SetRoi(Some(roi)) => {
+                                let touptek::Resolution { width, height } =
+                                    resolutions[cam.preview_size_index() as usize];
+                                let Roi { x, y, width, height } =
+                                    roi.aligned(width as u32, height as u32);
+                                cam.set_roi(x, y, width, height)
+                            }
+                            Command::SetPreviewSize(index) =>
+                                // The stored index may come from a camera with more sizes.
+                                cam.set_preview_size_index(
+                                    cmp::min(index, resolutions.len().saturating_sub(1))),
+                            Command::SetBitDepth { bits } => {
+                                cam.set_high_bit_depth(bits > 8);
+                                still_bits.set(bits)
+                            }
+                            Command::SetBinning { factor } if capabilities.binning =>
+                                cam.set_binning(factor),
+                            Command::SetBinning { .. } => (),
+                            Command::SetPixelFormat(format) => {
+                                cam.set_grayscale(format == PixelFormat::Grayscale);
+                                pixel_format.set(format)
+                            }
+                            Command::SetTriggerMode(_) if !capabilities.trigger => (),
+                            Command::SetTriggerMode(mode) => {
+                                cam.set_trigger_mode(match mode {
+                                    TriggerMode::FreeRun => touptek::TriggerMode::Video,
+                                    TriggerMode::Software => touptek::TriggerMode::Software,
+                                    TriggerMode::External => touptek::TriggerMode::External,
+                                });
+                                trigger_mode.set(mode)
+                            }
+                            Command::Trigger if trigger_mode.get() == TriggerMode::Software =>
+                                cam.trigger(1),
+                            Command::Trigger => (),
+                            Command::SetLed { index, on } =>
+                                cam.set_led_state(index, on),
+                            Command::SetStreaming(enabled) =>
+                                streaming.set(enabled),
+                            Command::SetReconnectMaxDelay { seconds } =>
+                                reconnect_max_delay.set(cmp::max(seconds, 1)),
+                            Command::AutoWhiteBalance =>
+                                cam.auto_white_balance_once(),
+                            Command::AutoExposureOnce => {
+                                cam.set_automatic_exposure(true);
+                                auto_exposure_once.set(Some((true, 0)))
+                            }
+                            Command::Snap(id) => {
+                                pending_snaps.borrow_mut().push_back(id);
+                                cam.snap_index(cam.preview_size_index())
+                            }
+                        }
+                    }
+                    true
+                };
 
+                let start_reconnecting = || {
+                    *reconnect.borrow_mut() = Some(Reconnect {
+                        camera_id: connected_id.borrow().clone(),
+                        attempt: 0,
+                        delay: 1,
+                        waited: 0,
+                    })
+                };
+
+                let deferred: Vec<Command> = deferred_cmds.borrow_mut().drain(..).collect();
+                if !run_commands(deferred) {
+                    send_or_return!(event_tx, Event::Disconnect);
+                    return
+                }
+
+                let pending_cmds = &cmd_rx;
                 let select = Select::new();
                 let mut cmd_rx = select.handle(&cmd_rx);
                 let mut cam_rx = select.handle(&cam_rx);
                 let mut hotplug_rx = select.handle(&hotplug_rx);
+                let mut tick_rx = select.handle(&tick_rx);
 
                 unsafe {
                     cmd_rx.add();
                     cam_rx.add();
                     hotplug_rx.add();
+                    tick_rx.add();
                 }
 
                 loop {
                     let id = select.wait();
 
                     if id == cmd_rx.id() {
-                        match cmd_rx.recv().unwrap() {
-                            Command::Connect(_) => (),
-                            Command::SetExposureTime { microseconds } =>
-                                cam.set_exposure_time(microseconds),
-                            Command::SetExposureGain { percents } =>
-                                cam.set_exposure_gain(percents),
-                            Command::SetColorTemperature { kelvin } =>
-                                cam.set_white_balance_temp_tint(
-                                    touptek::WhiteBalanceTempTint {
-                                        temperature: kelvin, ..cam.white_balance_temp_tint() }),
-                            Command::SetTint(tint) =>
-                                cam.set_white_balance_temp_tint(
-                                    touptek::WhiteBalanceTempTint {
-                                        tint: tint, ..cam.white_balance_temp_tint() }),
-                            Command::Snap =>
-                                cam.snap_index(cam.preview_size_index()),
+                        // Dragging a slider produces a command per pixel of movement; only
+                        // the latest value of each setting is worth sending to the camera.
+                        let mut commands = match cmd_rx.recv() {
+                            Ok(command) => vec![command],
+                            Err(_) => return
+                        };
+                        while let Ok(command) = pending_cmds.try_recv() {
+                            commands.push(command)
+                        }
+
+                        if !run_commands(commands) {
+                            // The select handles are removed as they are dropped, so it is
+                            // safe to leave the stream from the middle of the loop.
+                            send_or_return!(event_tx, Event::Disconnect);
+                            break
                         }
                     }
 
                     if id == cam_rx.id() {
-                        match cam_rx.recv().unwrap() {
+                        let event = match cam_rx.recv() {
+                            Ok(event) => event,
+                            Err(_) => {
+                                send_or_return!(event_tx, Event::Error(
+                                    "The camera stopped responding".to_string()));
+                                send_or_return!(event_tx, Event::Disconnect);
+                                start_reconnecting();
+                                break
+                            }
+                        };
+                        match event {
+                            touptek::Event::Image if !streaming.get() => {
+                                // The frame still has to be pulled to free its buffer.
+                                cam.pull_image(32);
+                            },
                             touptek::Event::Image => {
-                                let mut image = cam.pull_image(32);
-                                set_alpha(&mut image.data, 255);
-                                event_tx.send(Event::Image(image)).unwrap()
+                                let image = match pixel_format.get() {
+                                    PixelFormat::Color => {
+                                        let mut image = cam.pull_image(32);
+                                        set_alpha(&mut image.data, 255);
+                                        image
+                                    }
+                                    PixelFormat::Grayscale =>
+                                        gray_to_rgba(cam.pull_image(8))
+                                };
+                                frame_count.set(frame_count.get() + 1);
+                                // If the previous frame has not been taken yet, the UI has
+                                // not handled the event for it yet either.
+                                let previous = mem::replace(
+                                    &mut *latest_frame.lock().unwrap(), Some(image));
+                                let was_taken = previous.is_none();
+                                if was_taken {
+                                    send_or_return!(event_tx, Event::Image)
+                                }
                             },
                             touptek::Event::StillImage => {
-                                let mut image = cam.pull_still_image(32);
-                                set_alpha(&mut image.data, 255);
-                                event_tx.send(Event::StillImage(image)).unwrap()
+                                let mut image;
+                                if pixel_format.get() == PixelFormat::Grayscale {
+                                    // Gray pixels are expanded, so 16 bits are not kept.
+                                    image = gray_to_rgba(cam.pull_still_image(8));
+                                } else if still_bits.get() > 8 {
+                                    image = cam.pull_still_image(64);
+                                    set_alpha16(&mut image.data, 0xffff);
+                                } else {
+                                    image = cam.pull_still_image(32);
+                                    set_alpha(&mut image.data, 255);
+                                }
+                                // A still image that nobody asked for, e.g. one triggered
+                                // by the button on the camera, gets id 0.
+                                let id = pending_snaps.borrow_mut().pop_front().unwrap_or(0);
+                                send_or_return!(event_tx, Event::StillImage {
+                                    id: id, image: image })
                             },
                             touptek::Event::Disconnected => {
-                                event_tx.send(Event::Disconnect).unwrap();
+                                send_or_return!(event_tx, Event::Disconnect);
+                                start_reconnecting();
                                 break
                             },
+                            touptek::Event::TempTint => {
+                                let touptek::WhiteBalanceTempTint { temperature, tint } =
+                                    cam.white_balance_temp_tint();
+                                send_or_return!(event_tx, Event::WhiteBalance {
+                                    temperature: temperature, tint: tint })
+                            },
                             touptek::Event::Exposure => {
-                                /* ignore */
+                                if let Some((_, ticks)) = auto_exposure_once.get() {
+                                    auto_exposure_once.set(Some((true, ticks)))
+                                }
                             },
                             event => {
-                                panic!("unknown camera event: {:?}", event);
+                                send_or_return!(event_tx, Event::Error(
+                                    format!("Unknown camera event: {:?}", event)))
                             }
                         }
                     }
 
                     if id == hotplug_rx.id() {
                         hotplug_rx.recv().unwrap();
-                        event_tx.send(Event::Hotplug(touptek::Toupcam::enumerate())).unwrap()
+                        send_or_return!(event_tx, Event::Hotplug(touptek::Toupcam::enumerate()))
+                    }
+
+                    if id == tick_rx.id() {
+                        tick_rx.recv().unwrap();
+                        send_or_return!(event_tx, Event::Telemetry {
+                            temperature_c: cam.temperature() as f32 / 10., // in 0.1 °C units
+                            frame_count: frame_count.get()
+                        });
+
+                        match auto_exposure_once.get() {
+                            Some((true, ticks)) if ticks < AUTO_EXPOSURE_ONCE_MAX_S =>
+                                auto_exposure_once.set(Some((false, ticks + 1))),
+                            Some(_) => {
+                                cam.set_automatic_exposure(auto_exposure.get());
+                                auto_exposure_once.set(None);
+                                send_or_return!(event_tx, Event::Exposure {
+                                    time_us: cam.exposure_time(),
+                                    gain_pct: cam.exposure_gain()
+                                })
+                            }
+                            None => ()
+                        }
+
+                        let frames = frame_count.get() - frame_count_at_tick.get();
+                        frame_count_at_tick.set(frame_count.get());
+                        let elapsed = last_tick_at.get().elapsed();
+                        last_tick_at.set(Instant::now());
+                        let seconds =
+                            elapsed.as_secs() as f32 + elapsed.subsec_nanos() as f32 / 1e9;
+                        if seconds > 0. {
+                            send_or_return!(event_tx, Event::FrameRate(frames as f32 / seconds))
+                        }
                     }
                 }
             })
@@ -213,13 +1065,124 @@ fn camera_thread(event_tx: Sender<Event>, cmd_rx: Receiver<Command>) {
     })
 }
 
+fn next_snapshot_path(directory: &Path) -> PathBuf {
+    let mut index = 1;
+    loop {
+        let path = directory.join(format!("snap-{:04}.png", index));
+        if fs::metadata(&path).is_err() { return path }
+        index += 1
+    }
+}
+
+pub fn store_png(image: touptek::Image, path: &Path) -> Result<(), String> {
+    let touptek::Image { resolution: touptek::Resolution { width, height }, data, .. } = image;
+    let pixels =
+        if data.len() == (width * height * 8) as usize {
+            // RGBA with 16 bits per channel, in native byte order.
+            png::PixelsByColorType::RGBA16(data.chunks(2).map(|channel|
+                ((channel[1] as u16) << 8) | channel[0] as u16).collect())
+        } else {
+            png::PixelsByColorType::RGBA8(data)
+        };
+    let mut image = png::Image {
+        width: width, height: height,
+        pixels: pixels
+    };
+
+    png::store_png(&mut image, path).map_err(|err|
+        format!("cannot write {}: {}", path.display(), err))
+}
+
+pub fn save_still_image(image: touptek::Image, directory: &Path) -> Result<PathBuf, String> {
+    try!(fs::create_dir_all(directory).map_err(|err|
+        format!("cannot create {}: {}", directory.display(), err)));
+
+    let path = next_snapshot_path(directory);
+    try!(store_png(image, &path));
+    Ok(path)
+}
+
 fn set_alpha(rgba: &mut Vec<u8>, alpha: u8) {
-    let alpha = simd::u8x16::new(0, 0, 0, alpha, 0, 0, 0, alpha,
-                                 0, 0, 0, alpha, 0, 0, 0, alpha);
+    let alpha_x16 = simd::u8x16::new(0, 0, 0, alpha, 0, 0, 0, alpha,
+                                     0, 0, 0, alpha, 0, 0, 0, alpha);
     let mut index = 0;
     let length = rgba.len();
-    while index < length {
-        (simd::u8x16::load( rgba, index) | alpha).store(rgba, index);
+    while index + 16 <= length {
+        (simd::u8x16::load(rgba, index) | alpha_x16).store(rgba, index);
         index += 16
     }
+
+    // A frame with an odd number of pixels leaves a few that do not fill a vector.
+    for pixel in rgba[index..].chunks_mut(4) {
+        if pixel.len() < 4 { break }
+        pixel[3] |= alpha
+    }
+}
+
+fn gray_to_rgba(image: touptek::Image) -> touptek::Image {
+    let mut rgba = Vec::with_capacity(image.data.len() * 4);
+    for &value in &image.data {
+        rgba.push(value);
+        rgba.push(value);
+        rgba.push(value);
+        rgba.push(255);
+    }
+    touptek::Image { data: rgba, ..image }
+}
+
+fn set_alpha16(rgba: &mut Vec<u8>, alpha: u16) {
+    for pixel in rgba.chunks_mut(8) {
+        if pixel.len() < 8 { break }
+        pixel[6] = alpha as u8;
+        pixel[7] = (alpha >> 8) as u8;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Capabilities, FLAG_MONO, FLAG_BINSKIP_SUPPORTED, FLAG_TRIGGER_EXTERNAL};
+    use super::{Roi, Command, coalesce};
+
+    #[test]
+    fn capabilities_follow_model_flags() {
+        assert_eq!(Capabilities::from_model_flags(0),
+                   Capabilities { monochrome: false, binning: false, trigger: false });
+        assert_eq!(Capabilities::from_model_flags(FLAG_MONO | FLAG_TRIGGER_EXTERNAL),
+                   Capabilities { monochrome: true, binning: false, trigger: true });
+        assert_eq!(Capabilities::from_model_flags(FLAG_BINSKIP_SUPPORTED | 0x1),
+                   Capabilities { monochrome: false, binning: true, trigger: false });
+    }
+
+    #[test]
+    fn roi_is_aligned_to_even_pixels() {
+        assert_eq!(Roi { x: 101, y: 50, width: 100, height: 31 }.aligned(640, 480),
+                   Roi { x: 100, y: 50, width: 102, height: 32 });
+    }
+
+    #[test]
+    fn roi_grows_to_minimum_size_within_sensor() {
+        assert_eq!(Roi { x: 100, y: 475, width: 3, height: 4 }.aligned(640, 480),
+                   Roi { x: 100, y: 464, width: 16, height: 16 });
+    }
+
+    #[test]
+    fn later_setting_supersedes_earlier() {
+        assert_eq!(coalesce(vec![Command::SetGamma(90), Command::SetHue(10),
+                                 Command::SetGamma(110)]),
+                   vec![Command::SetHue(10), Command::SetGamma(110)]);
+    }
+
+    #[test]
+    fn settings_are_not_coalesced_across_barriers() {
+        assert_eq!(coalesce(vec![Command::SetPreviewSize(1), Command::Snap(7),
+                                 Command::SetPreviewSize(2), Command::SetPreviewSize(3)]),
+                   vec![Command::SetPreviewSize(1), Command::Snap(7),
+                        Command::SetPreviewSize(3)]);
+        assert_eq!(coalesce(vec![Command::SetExposureTime { microseconds: 1000 },
+                                 Command::AutoExposureOnce,
+                                 Command::SetExposureTime { microseconds: 2000 }]),
+                   vec![Command::SetExposureTime { microseconds: 1000 },
+                        Command::AutoExposureOnce,
+                        Command::SetExposureTime { microseconds: 2000 }]);
+    }
 }
@@ -2,16 +2,120 @@ extern crate xdg;
 extern crate serde_json;
 
 use std::rc::Rc;
+use std::collections::HashMap;
 use std::fs::File;
+use std::io;
+use std::fmt;
+use std::path::{Path, PathBuf};
 
-use property::Property;
+use self::serde_json::Value;
 
-#[derive(Serialize, Deserialize, Default)]
+use property::{self, Property};
+use camera;
+
+// Incremented whenever a field is renamed or changes meaning; see `migrate`.
+const CONFIG_VERSION: u64 = 1;
+
+#[derive(Serialize, Deserialize)]
 pub struct Config {
+    #[serde(default)]
+    version: u64,
     exposure_time_us: Rc<Property<u32>>,
     exposure_gain_pct: Rc<Property<u16>>,
     color_temperature_k: Rc<Property<u32>>,
     tint: Rc<Property<u32>>,
+    gamma: Rc<Property<u32>>,
+    contrast: Rc<Property<i32>>,
+    saturation: Rc<Property<u32>>,
+    hue: Rc<Property<i32>>,
+    sharpness: Rc<Property<u32>>,
+    #[serde(default)]
+    snapshot_dir: Option<String>,
+    #[serde(default)]
+    idle_background: Option<String>,
+    #[serde(default)]
+    reconnect_max_delay_s: Option<u32>,
+    #[serde(default)]
+    ui_scale: Option<f32>,
+    #[serde(default)]
+    frame_alpha: Option<f32>,
+    #[serde(default)]
+    window_width: Option<u32>,
+    #[serde(default)]
+    window_height: Option<u32>,
+    #[serde(default)]
+    window_x: Option<i32>,
+    #[serde(default)]
+    window_y: Option<i32>,
+    #[serde(default)]
+    fullscreen: Option<bool>,
+    #[serde(default)]
+    vsync: Option<bool>,
+    #[serde(default)]
+    max_fps: Option<f32>,
+    #[serde(default)]
+    crosshair: Rc<Property<bool>>,
+    #[serde(default)]
+    crosshair_color: Option<(f32, f32, f32)>,
+    #[serde(default)]
+    pixels_per_mm: Option<f32>,
+    #[serde(default)]
+    thirds: Rc<Property<bool>>,
+    #[serde(default)]
+    grid: Rc<Property<bool>>,
+    #[serde(default)]
+    grid_pitch_px: Rc<Property<u32>>,
+    #[serde(default)]
+    presets: HashMap<String, CameraSettings>,
+}
+
+/// Camera settings saved under a name, so that they can be brought back later.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct CameraSettings {
+    exposure_time_us: u32,
+    exposure_gain_pct: u16,
+    color_temperature_k: u32,
+    tint: u32,
+}
+
+const DEFAULT_WINDOW_SIZE: (u32, u32) = (1024, 768);
+const MIN_WINDOW_SIZE: u32 = 200;
+const MAX_WINDOW_SIZE: u32 = 16384;
+
+impl Default for Config {
+    fn default() -> Config {
+        Config {
+            version: CONFIG_VERSION,
+            exposure_time_us: Property::new(camera::DEFAULT_EXPOSURE_TIME_US),
+            exposure_gain_pct: Property::new(camera::DEFAULT_EXPOSURE_GAIN_PCT),
+            color_temperature_k: Property::new(camera::DEFAULT_COLOR_TEMPERATURE_K),
+            tint: Property::new(camera::DEFAULT_TINT),
+            gamma: Property::new(camera::DEFAULT_GAMMA),
+            contrast: Property::new(camera::DEFAULT_CONTRAST),
+            saturation: Property::new(camera::DEFAULT_SATURATION),
+            hue: Property::new(camera::DEFAULT_HUE),
+            sharpness: Property::new(camera::DEFAULT_SHARPNESS),
+            snapshot_dir: None,
+            idle_background: None,
+            reconnect_max_delay_s: None,
+            ui_scale: None,
+            frame_alpha: None,
+            window_width: None,
+            window_height: None,
+            window_x: None,
+            window_y: None,
+            fullscreen: None,
+            vsync: None,
+            max_fps: None,
+            crosshair: Property::new(false),
+            crosshair_color: None,
+            pixels_per_mm: None,
+            thirds: Property::new(false),
+            grid: Property::new(false),
+            grid_pitch_px: Property::new(100),
+            presets: HashMap::new(),
+        }
+    }
 }
 
 impl Config {
@@ -19,24 +123,256 @@ impl Config {
     pub fn exposure_gain_pct(&self) -> Rc<Property<u16>> { self.exposure_gain_pct.clone() }
     pub fn color_temperature_k(&self) -> Rc<Property<u32>> { self.color_temperature_k.clone() }
     pub fn tint(&self) -> Rc<Property<u32>> { self.tint.clone() }
+    pub fn gamma(&self) -> Rc<Property<u32>> { self.gamma.clone() }
+    pub fn contrast(&self) -> Rc<Property<i32>> { self.contrast.clone() }
+    pub fn saturation(&self) -> Rc<Property<u32>> { self.saturation.clone() }
+    pub fn hue(&self) -> Rc<Property<i32>> { self.hue.clone() }
+    pub fn sharpness(&self) -> Rc<Property<u32>> { self.sharpness.clone() }
+
+    /// Scale of the UI relative to the framebuffer density.
+    pub fn ui_scale(&self) -> f32 { self.ui_scale.unwrap_or(1.) }
+    pub fn set_ui_scale(&mut self, scale: f32) { self.ui_scale = Some(scale) }
+
+    /// Opacity of the controls over the camera image.
+    pub fn frame_alpha(&self) -> f32 { self.frame_alpha.unwrap_or(0.8) }
+    pub fn set_frame_alpha(&mut self, alpha: f32) { self.frame_alpha = Some(alpha) }
+
+    pub fn window_size(&self) -> (u32, u32) {
+        let valid = |size: u32| size >= MIN_WINDOW_SIZE && size <= MAX_WINDOW_SIZE;
+        match (self.window_width, self.window_height) {
+            (Some(width), Some(height)) if valid(width) && valid(height) => (width, height),
+            _ => DEFAULT_WINDOW_SIZE
+        }
+    }
+
+    pub fn window_position(&self) -> Option<(i32, i32)> {
+        match (self.window_x, self.window_y) {
+            (Some(x), Some(y)) => Some((x, y)),
+            _ => None
+        }
+    }
+
+    pub fn set_window_geometry(&mut self, size: (u32, u32), position: (i32, i32)) {
+        self.window_width = Some(size.0);
+        self.window_height = Some(size.1);
+        self.window_x = Some(position.0);
+        self.window_y = Some(position.1);
+    }
+
+    pub fn fullscreen(&self) -> bool { self.fullscreen.unwrap_or(false) }
+    pub fn set_fullscreen(&mut self, fullscreen: bool) { self.fullscreen = Some(fullscreen) }
+
+    /// Whether frames are drawn in step with the display refresh.
+    pub fn vsync(&self) -> bool { self.vsync.unwrap_or(true) }
+
+    /// Most frames drawn per second, if limited; a lower limit saves power.
+    pub fn max_fps(&self) -> Option<f32> {
+        self.max_fps.and_then(|fps| if fps > 0. { Some(fps) } else { None })
+    }
+
+    pub fn crosshair(&self) -> Rc<Property<bool>> { self.crosshair.clone() }
+
+    /// Color of the crosshair as red, green and blue, from 0 to 1.
+    pub fn crosshair_color(&self) -> (f32, f32, f32) {
+        self.crosshair_color.unwrap_or((1., 0.5, 0.))
+    }
+
+    /// Image pixels per millimeter in the object plane, if known; used for measurements.
+    pub fn pixels_per_mm(&self) -> Option<f32> { self.pixels_per_mm }
+
+    pub fn thirds(&self) -> Rc<Property<bool>> { self.thirds.clone() }
+    pub fn grid(&self) -> Rc<Property<bool>> { self.grid.clone() }
+
+    /// Distance between the lines of the grid, in image pixels.
+    pub fn grid_pitch_px(&self) -> Rc<Property<u32>> { self.grid_pitch_px.clone() }
+
+    /// PNG file shown instead of the camera image while no camera is connected.
+    pub fn idle_background(&self) -> Option<PathBuf> {
+        self.idle_background.as_ref().map(PathBuf::from)
+    }
+
+    /// Longest wait between attempts to reconnect to a camera that went away.
+    pub fn reconnect_max_delay_s(&self) -> u32 {
+        self.reconnect_max_delay_s.unwrap_or(camera::RECONNECT_MAX_DELAY_S)
+    }
+
+    /// Names of the saved presets, in alphabetical order.
+    pub fn preset_names(&self) -> Vec<String> {
+        let mut names: Vec<String> = self.presets.keys().cloned().collect();
+        names.sort();
+        names
+    }
+
+    /// Saves the current settings under `name`, replacing any preset with the same name.
+    /// Returns `true` if one was replaced.
+    pub fn save_preset(&mut self, name: &str) -> bool {
+        let settings = CameraSettings {
+            exposure_time_us: self.exposure_time_us.get(),
+            exposure_gain_pct: self.exposure_gain_pct.get(),
+            color_temperature_k: self.color_temperature_k.get(),
+            tint: self.tint.get(),
+        };
+        self.presets.insert(name.to_string(), settings).is_some()
+    }
+
+    /// Changes the current settings to those saved under `name`. Returns `false` if there
+    /// is no such preset.
+    pub fn apply_preset(&self, name: &str) -> bool {
+        let settings = match self.presets.get(name) {
+            Some(settings) => settings.clone(),
+            None => return false
+        };
+        property::batch(|batch| {
+            batch.set(&self.exposure_time_us, settings.exposure_time_us);
+            batch.set(&self.exposure_gain_pct, settings.exposure_gain_pct);
+            batch.set(&self.color_temperature_k, settings.color_temperature_k);
+            batch.set(&self.tint, settings.tint)
+        });
+        true
+    }
+
+    /// Returns `false` if there is no preset called `name`.
+    pub fn delete_preset(&mut self, name: &str) -> bool {
+        self.presets.remove(name).is_some()
+    }
+
+    pub fn snapshot_dir(&self) -> PathBuf {
+        match self.snapshot_dir {
+            Some(ref dir) => PathBuf::from(dir),
+            None => xdg_dirs().get_data_home().join("snapshots")
+        }
+    }
 }
 
 fn xdg_dirs() -> xdg::BaseDirectories {
     xdg::BaseDirectories::with_prefix("psychic_seamstress")
 }
 
-pub fn load() -> Config {
-    match xdg_dirs().find_config_file("config.json") {
-        None => Config::default(),
+// Brings a config written by an older version up to date.
+fn migrate(value: Value) -> Value {
+    let mut fields = match value {
+        Value::Object(fields) => fields,
+        value => return value
+    };
+
+    // Version 0 had no version field, and nothing has been renamed since, so it only
+    // lacks the fields added later.
+    fields.insert("version".to_string(), Value::U64(CONFIG_VERSION));
+
+    // Fields that did not exist in older versions take their default values.
+    let mut config = match serde_json::to_value(&Config::default()) {
+        Value::Object(defaults) => defaults,
+        _ => unreachable!()
+    };
+    for (key, value) in fields {
+        config.insert(key, value);
+    }
+    Value::Object(config)
+}
+
+#[derive(Debug)]
+pub enum ConfigError {
+    Io(io::Error),
+    Json(serde_json::Error),
+}
+
+impl From<io::Error> for ConfigError {
+    fn from(err: io::Error) -> ConfigError { ConfigError::Io(err) }
+}
+
+impl From<serde_json::Error> for ConfigError {
+    fn from(err: serde_json::Error) -> ConfigError { ConfigError::Json(err) }
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            ConfigError::Io(ref err) => write!(f, "{}", err),
+            ConfigError::Json(ref err) => write!(f, "{}", err),
+        }
+    }
+}
+
+/// Loads the config from `path`, or from the usual location if it is `None`;
+/// returns the defaults if there is none yet.
+pub fn load(path: Option<&Path>) -> Result<Config, ConfigError> {
+    let path = match path {
+        Some(path) if path.exists() => Some(path.to_path_buf()),
+        Some(_) => None,
+        None => xdg_dirs().find_config_file("config.json")
+    };
+    match path {
+        None => Ok(Config::default()),
         Some(path) => {
-            let mut file = File::open(path).unwrap();
-            serde_json::from_reader(&mut file).unwrap()
+            let mut file = try!(File::open(&path));
+            let value = try!(serde_json::from_reader(&mut file));
+            Ok(try!(serde_json::from_value(migrate(value))))
         }
     }
 }
 
-pub fn store(config: &Config) {
-    let path = xdg_dirs().place_config_file("config.json").unwrap();
-    let mut file = File::create(path).unwrap();
-    serde_json::to_writer_pretty(&mut file, config).unwrap()
+pub fn store(config: &Config, path: Option<&Path>) -> Result<(), ConfigError> {
+    let path = match path {
+        Some(path) => path.to_path_buf(),
+        None => try!(xdg_dirs().place_config_file("config.json"))
+    };
+    let mut file = try!(File::create(path));
+    Ok(try!(serde_json::to_writer_pretty(&mut file, config)))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+    use std::fs;
+    use camera;
+    use super::{serde_json, load, store, migrate, Config, CONFIG_VERSION};
+
+    #[test]
+    fn properties_survive_round_trip() {
+        let config = Config::default();
+        config.exposure_time_us().set(5000);
+        config.tint().set(900);
+        config.gamma().set(150);
+        config.hue().set(-30);
+        config.grid().set(true);
+
+        let json = serde_json::to_string(&config).unwrap();
+        let loaded: Config = serde_json::from_str(&json).unwrap();
+        assert_eq!(loaded.exposure_time_us().get(), 5000);
+        assert_eq!(loaded.tint().get(), 900);
+        assert_eq!(loaded.gamma().get(), 150);
+        assert_eq!(loaded.hue().get(), -30);
+        assert_eq!(loaded.grid().get(), true);
+    }
+
+    #[test]
+    fn version_0_is_migrated() {
+        let json = r#"{"exposure_time_us": 5000, "exposure_gain_pct": 200,
+                       "color_temperature_k": 4000, "tint": 900}"#;
+        let value = serde_json::from_str(json).unwrap();
+        let config: Config = serde_json::from_value(migrate(value)).unwrap();
+        assert_eq!(config.version, CONFIG_VERSION);
+        assert_eq!(config.exposure_time_us().get(), 5000);
+        assert_eq!(config.exposure_gain_pct().get(), 200);
+        assert_eq!(config.color_temperature_k().get(), 4000);
+        assert_eq!(config.tint().get(), 900);
+        assert_eq!(config.gamma().get(), camera::DEFAULT_GAMMA);
+        assert_eq!(config.saturation().get(), camera::DEFAULT_SATURATION);
+        assert_eq!(config.grid_pitch_px().get(), 100);
+        assert!(config.preset_names().is_empty());
+    }
+
+    #[test]
+    fn settings_are_loaded_from_given_file() {
+        let path = env::temp_dir().join("psychic_seamstress_test_config.json");
+        let config = Config::default();
+        config.exposure_time_us().set(5000);
+        config.thirds().set(true);
+        store(&config, Some(path.as_path())).unwrap();
+
+        let loaded = load(Some(path.as_path())).unwrap();
+        fs::remove_file(&path).unwrap();
+        assert_eq!(loaded.exposure_time_us().get(), 5000);
+        assert_eq!(loaded.thirds().get(), true);
+    }
 }
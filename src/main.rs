@@ -1,4 +1,4 @@
-#![feature(const_fn, iter_arith, plugin, custom_derive, mpsc_select, drain)]
+#![feature(const_fn, iter_arith, plugin, custom_derive, mpsc_select, drain, borrow_state)]
 #![allow(unused_unsafe, dead_code)]
 #![plugin(serde_macros)]
 
@@ -10,21 +10,62 @@ extern crate serde;
 extern crate touptek;
 
 use std::rc::Rc;
-use std::path::Path;
+use std::cmp;
+use std::env;
+use std::process;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
 use std::thread;
 use std::sync::mpsc::channel;
 
 use glfw::Context as GlfwContext;
 use nanovg::Context as NvgContext;
 
-use property::Property;
+use property::{self, Property};
 use ui::*;
 
 pub mod property;
 pub mod config;
 pub mod camera;
+pub mod recorder;
+pub mod processing;
 pub mod ui;
 
+// Only every Nth pixel of a frame contributes to the histogram.
+const HISTOGRAM_STRIDE: usize = 16;
+
+// How often the FPS readout changes, in seconds, and how much each frame affects it.
+const FPS_UPDATE_INTERVAL: f64 = 0.5;
+const FPS_SMOOTHING: f64 = 0.1;
+
+// Longest delay before a camera event is handled while the UI is idle, in seconds.
+const EVENT_WAIT_TIMEOUT: f64 = 0.01;
+
+// Color temperature and tint for common kinds of lighting.
+const WHITE_BALANCE_PRESETS: &'static [(&'static str, u32, u32)] = &[
+    ("Daylight",    5500, 1000),
+    ("Cloudy",      6500, 1000),
+    ("Tungsten",    3200, 1000),
+    ("Fluorescent", 4000, 1000),
+];
+
+const UI_SCALE_STEP: f32 = 1.1;
+const UI_SCALE_MIN: f32 = 0.5;
+const UI_SCALE_MAX: f32 = 4.;
+
+const FRAME_ALPHA_STEP: f32 = 0.1;
+
+// The exposure time slider is in milliseconds, but moves in steps of a microsecond, so
+// that any exposure time the camera allows can be shown and set exactly.
+const EXPOSURE_STEP_MS: f32 = 0.001;
+
+fn exposure_us_to_ms(us: u32) -> f32 { us as f32 / 1000. }
+fn exposure_ms_to_us(ms: f32) -> u32 { (ms * 1000.).round() as u32 }
+
+// Built into the executable, so that they do not depend on the working directory.
+const NO_SIGNAL_PNG: &'static [u8] = include_bytes!("../res/nosignal.png");
+const ROBOTO_TTF: &'static [u8] = include_bytes!("../res/Roboto-Regular.ttf");
+
 macro_rules! gl {
     ($e: expr) => ({
         use gl::*;
@@ -33,12 +74,188 @@ macro_rules! gl {
     })
 }
 
+fn store_window_geometry(config: &mut config::Config, window: &glfw::Window) {
+    let (width, height) = window.get_size();
+    config.set_window_geometry((width as u32, height as u32), window.get_pos())
+}
+
+// The windowed geometry is kept in the config while in fullscreen, to restore it later.
+fn set_fullscreen(glfw: &mut glfw::Glfw, window: &mut glfw::Window,
+                  config: &mut config::Config, fullscreen: bool) {
+    if fullscreen {
+        store_window_geometry(config, window);
+        let switched = glfw.with_primary_monitor(|_, monitor| {
+            let monitor = match monitor { Some(monitor) => monitor, None => return false };
+            match monitor.get_video_mode() {
+                Some(mode) => {
+                    window.set_monitor(glfw::WindowMode::FullScreen(monitor), 0, 0,
+                                       mode.width, mode.height, Some(mode.refresh_rate));
+                    true
+                }
+                None => false
+            }
+        });
+        if !switched { return }
+    } else {
+        let (width, height) = config.window_size();
+        let (x, y) = config.window_position().unwrap_or((0, 0));
+        window.set_monitor(glfw::WindowMode::Windowed, x, y, width, height, None)
+    }
+    config.set_fullscreen(fullscreen)
+}
+
+fn store_config(config: &config::Config, path: Option<&Path>) {
+    if let Err(err) = config::store(config, path) {
+        let _ = writeln!(io::stderr(), "Cannot save settings: {}", err);
+    }
+}
+
+// The font is embedded, so this can only fail if the executable itself is damaged,
+// and then no text could be shown anyway.
+fn load_font(nvg: &NvgContext) {
+    nvg.create_font_mem("Roboto", ROBOTO_TTF)
+       .expect("cannot load the embedded Roboto font");
+}
+
+fn load_idle_background(image: &Image, config: &config::Config) {
+    if let Some(path) = config.idle_background() {
+        match image.from_png_file(&path) {
+            Ok(()) => return,
+            Err(err) => {
+                let _ = writeln!(io::stderr(), "Cannot load idle background {}: {}",
+                                 path.display(), err);
+            }
+        }
+    }
+    image.from_png_bytes(NO_SIGNAL_PNG).unwrap()
+}
+
+// Reads back what has been rendered so far, before the buffers are swapped.
+fn save_screenshot(width: i32, height: i32, path: &Path) -> Result<(), String> {
+    let mut pixels = vec![0u8; (width * height * 4) as usize];
+    gl!(ReadPixels(0, 0, width, height, RGBA, UNSIGNED_BYTE, pixels.as_mut_ptr() as *mut _));
+
+    // OpenGL returns the bottom row first.
+    let pixels = pixels.chunks(width as usize * 4).rev()
+                       .flat_map(|row| row.iter().cloned()).collect();
+    let mut image = png::Image {
+        width: width as u32, height: height as u32,
+        pixels: png::PixelsByColorType::RGBA8(pixels)
+    };
+    png::store_png(&mut image, path).map_err(|err|
+        format!("cannot write {}: {}", path.display(), err))
+}
+
+const USAGE: &'static str = "\
+Usage: psychic_seamstress [options]
+
+Options:
+    --camera <id>         connect to the camera with this unique id
+    --config <file>       keep the settings in this file
+    --width <pixels>      width of the window
+    --height <pixels>     height of the window
+    --screenshot <file>   render a single frame into a PNG file and exit, showing
+                          the settings from --config
+    --no-vsync            draw frames without waiting for the display refresh
+    --max-fps <fps>       draw at most this many frames per second
+    --help                show this message";
+
+#[derive(Default)]
+struct Args {
+    camera: Option<String>,
+    config: Option<PathBuf>,
+    width: Option<u32>,
+    height: Option<u32>,
+    // With `--screenshot <file>`, a single frame is rendered into a PNG file without
+    // showing the window or connecting to a camera, so that the layout can be checked;
+    // the widgets show the settings loaded from `--config <file>`.
+    screenshot: Option<PathBuf>,
+    no_vsync: bool,
+    max_fps: Option<f32>,
+    help: bool,
+}
+
+fn parse_args<I>(mut args: I) -> Result<Args, String> where I: Iterator<Item=String> {
+    fn value<I>(args: &mut I, option: &str) -> Result<String, String>
+            where I: Iterator<Item=String> {
+        args.next().ok_or(format!("{} requires a value", option))
+    }
+
+    fn pixels<I>(args: &mut I, option: &str) -> Result<u32, String>
+            where I: Iterator<Item=String> {
+        match try!(value(args, option)).parse() {
+            Ok(pixels) if pixels > 0 => Ok(pixels),
+            _ => Err(format!("{} requires a positive number of pixels", option))
+        }
+    }
+
+    let mut parsed = Args::default();
+    while let Some(arg) = args.next() {
+        match &arg[..] {
+            "--camera"     => parsed.camera = Some(try!(value(&mut args, &arg))),
+            "--config"     => parsed.config = Some(PathBuf::from(try!(value(&mut args, &arg)))),
+            "--width"      => parsed.width = Some(try!(pixels(&mut args, &arg))),
+            "--height"     => parsed.height = Some(try!(pixels(&mut args, &arg))),
+            "--screenshot" =>
+                parsed.screenshot = Some(PathBuf::from(try!(value(&mut args, &arg)))),
+            "--no-vsync"   => parsed.no_vsync = true,
+            "--max-fps"    => parsed.max_fps = match try!(value(&mut args, &arg)).parse() {
+                Ok(fps) if fps > 0. => Some(fps),
+                _ => return Err(format!("{} requires a positive number", arg))
+            },
+            "--help"       => parsed.help = true,
+            _ => return Err(format!("unknown argument {}", arg))
+        }
+    }
+    Ok(parsed)
+}
+
 fn main() {
-    let config = config::load();
+    let args = match parse_args(env::args().skip(1)) {
+        Ok(ref args) if args.help => {
+            println!("{}", USAGE);
+            return
+        }
+        Ok(args) => args,
+        Err(err) => {
+            let _ = writeln!(io::stderr(), "{}\n\n{}", err, USAGE);
+            process::exit(2)
+        }
+    };
+    let screenshot_path = args.screenshot.clone();
+    let config_path = args.config.as_ref().map(|path| path.as_path());
+
+    let (mut config, config_error) = match config::load(config_path) {
+        Ok(config) => (config, None),
+        Err(err) => (config::Config::default(), Some(err))
+    };
+
+    // A screenshot that was asked to show some settings must not show the defaults instead.
+    if screenshot_path.is_some() {
+        let config_error = match (config_path, &config_error) {
+            (_, &Some(ref err)) => Some(format!("{}", err)),
+            (Some(path), _) if !path.exists() =>
+                Some(format!("{} does not exist", path.display())),
+            _ => None
+        };
+        if let Some(err) = config_error {
+            let _ = writeln!(io::stderr(), "Cannot load settings: {}", err);
+            process::exit(1)
+        }
+    }
 
     enum Event {
         Camera(camera::Event),
         Glfw(glfw::WindowEvent),
+        SelectDevice(usize),
+        WhiteBalancePreset(usize),
+        SavePreset,
+        ApplyPreset,
+        DeletePreset,
+        Disconnect,
+        Record(bool),
+        CaptureDarkFrame,
+        SetTheme(Theme),
     }
     let (event_tx, event_rx) = channel();
 
@@ -53,10 +270,16 @@ fn main() {
         });
     }
 
-    config.exposure_time_us().propagate(camera.exposure_time_us(), |x| *x);
-    config.exposure_gain_pct().propagate(camera.exposure_gain_pct(), |x| *x);
-    config.color_temperature_k().propagate(camera.color_temperature_k(), |x| *x);
-    config.tint().propagate(camera.tint(), |x| *x);
+    camera.exposure_time_us().link(config.exposure_time_us());
+    camera.exposure_gain_pct().link(config.exposure_gain_pct());
+    camera.color_temperature_k().link(config.color_temperature_k());
+    camera.tint().link(config.tint());
+    camera.gamma().link(config.gamma());
+    camera.contrast().link(config.contrast());
+    camera.saturation().link(config.saturation());
+    camera.hue().link(config.hue());
+    camera.sharpness().link(config.sharpness());
+    camera.set_reconnect_max_delay(config.reconnect_max_delay_s());
 
     let mut glfw = glfw::init(glfw::FAIL_ON_ERRORS).unwrap();
     glfw.window_hint(glfw::WindowHint::ContextVersion(3, 2));
@@ -64,15 +287,33 @@ fn main() {
     glfw.window_hint(glfw::WindowHint::OpenGlProfile(glfw::OpenGlProfileHint::Core));
     glfw.window_hint(glfw::WindowHint::OpenGlDebugContext(true));
     // glfw.window_hint(glfw::WindowHint::Resizable(false));
+    if screenshot_path.is_some() {
+        glfw.window_hint(glfw::WindowHint::Visible(false));
+    }
 
+    let (window_width, window_height) = config.window_size();
+    let (window_width, window_height) =
+        (args.width.unwrap_or(window_width), args.height.unwrap_or(window_height));
     let (mut window, glfw_event_rx) =
-        glfw.create_window(1024, 768, "~psychic seamstress~", glfw::WindowMode::Windowed)
+        glfw.create_window(window_width, window_height, "~psychic seamstress~",
+                           glfw::WindowMode::Windowed)
             .expect("Failed to create GLFW window.");
+    if let Some((x, y)) = config.window_position() {
+        window.set_pos(x, y)
+    }
+    if config.fullscreen() && screenshot_path.is_none() {
+        set_fullscreen(&mut glfw, &mut window, &mut config, true)
+    }
     window.set_mouse_button_polling(true);
     window.set_key_polling(true);
     window.set_cursor_pos_polling(true);
     window.set_scroll_polling(true);
+    window.set_char_polling(true);
     window.make_current();
+    let vsync = config.vsync() && !args.no_vsync;
+    glfw.set_swap_interval(if vsync { 1 } else { 0 });
+    // Shortest time between two frames, in seconds; with vsync, the display limits it too.
+    let frame_interval = args.max_fps.or(config.max_fps()).map_or(0., |fps| 1. / fps as f64);
     {
         let event_tx = event_tx.clone();
         thread::spawn(move || {
@@ -93,43 +334,85 @@ fn main() {
     gl!(Enable(SCISSOR_TEST));
 
     let nvg = NvgContext::create_gl3(nanovg::ANTIALIAS | nanovg::STENCIL_STROKES);
-    nvg.create_font_mem("Roboto", include_bytes!("../res/Roboto-Regular.ttf")).unwrap();
+    load_font(&nvg);
+
+    let mut cfg_layout = BoxLayout::vert();
+    cfg_layout.set_spacing(10.);
 
-    let mut cfg_layout = BoxLayout::vert(&nvg);
+    // Device picker
+    let device_picker = Dropdown::new(vec![], 0);
+    let device_names = device_picker.options();
+    let device_selected = device_picker.selected();
+    device_picker.selected().notify(&event_tx, |index| Event::SelectDevice(*index));
+    cfg_layout.add(Box::new(device_picker));
+    let mut devices = vec![];
 
-    fn slider<'a>(nvg: &'a NvgContext, name: String, unit: String, position: SliderPosition) ->
-                        (BoxLayout<'a>, Rc<Property<SliderPosition>>) {
-        let label = Label::new(&nvg);
-        let slider = Slider::new(&nvg, position);
+    // Disconnect button
+    let disconnect_button = Button::new("Disconnect".to_string());
+    disconnect_button.on_click(|| event_tx.send(Event::Disconnect).unwrap());
+    cfg_layout.add(Box::new(disconnect_button));
 
-        let position = slider.position();
-        let current_position = slider.current_position();
-        current_position.propagate(label.text(), move |value|
-            format!("{}: {}{}", name, value, unit));
+    fn slider<'a>(name: String, unit: String, tooltip: String, position: SliderPosition,
+                  scale: SliderScale)
+                 -> (BoxLayout<'a>, Rc<Property<SliderPosition>>, Rc<Property<bool>>) {
+        let label = Label::new();
+        let input = TextInput::new();
+        let unit_label = Label::new();
+        let slider = Slider::new(position).with_tooltip(tooltip).with_scale(scale)
+                                          .with_unit(unit.clone());
+        slider.set_animated(true);
 
-        let mut layout = BoxLayout::vert(&nvg);
-        layout.add(Box::new(label));
+        let (position, enabled) = (slider.position(), slider.enabled());
+        label.text().set(format!("{}: ", name));
+        unit_label.text().set(unit);
+        input.value().link(slider.current_position());
+        input.enabled().link(slider.enabled());
+
+        let mut header = BoxLayout::horz();
+        header.set_alignment(Alignment::Center);
+        header.add(Box::new(label));
+        header.add(Box::new(input));
+        header.add(Box::new(unit_label));
+
+        let mut layout = BoxLayout::vert();
+        layout.add(Box::new(header));
         layout.add(Box::new(slider));
 
-        (layout, position)
+        (layout, position, enabled)
     }
 
+    // What the connected camera can do; controls for anything else are disabled. Until
+    // a camera says otherwise, it is assumed to do everything.
+    let capabilities: Rc<Property<Option<camera::Capabilities>>> = Property::new(None);
+    let color_supported = capabilities.map(|capabilities|
+        capabilities.as_ref().map_or(true, |capabilities| !capabilities.monochrome));
+    let binning_supported = capabilities.map(|capabilities|
+        capabilities.as_ref().map_or(true, |capabilities| capabilities.binning));
+    let trigger_supported = capabilities.map(|capabilities|
+        capabilities.as_ref().map_or(true, |capabilities| capabilities.trigger));
+
+    cfg_layout.add(Box::new(Separator::new(Direction::Horizontal)));
+
     // Exposure time slider
-    let (widget, exposure_time_pos) = slider(&nvg,
+    let (widget, exposure_time_pos, _) = slider(
         "Exposure time".to_string(), "ms".to_string(),
-        SliderPosition { minimum: 1., maximum: 2000., step: 5., current: 0. });
+        "How long the sensor integrates light for each frame".to_string(),
+        SliderPosition { minimum: 1., maximum: 2000., step: EXPOSURE_STEP_MS, current: 0. },
+        SliderScale::Logarithmic);
     exposure_time_pos.write(|slider|
-        slider.current = (config.exposure_time_us().get() / 1000) as f32);
+        slider.current = exposure_us_to_ms(config.exposure_time_us().get()));
     config.exposure_time_us().derive(exposure_time_pos.clone(),
-       |slider, value| SliderPosition { current: (value / 1000) as f32, ..*slider },
-       |slider|        (slider.current * 1000.) as u32);
+       |slider, value| SliderPosition { current: exposure_us_to_ms(value), ..*slider },
+       |slider|        exposure_ms_to_us(slider.current));
     cfg_layout.add(Box::new(widget));
 
     // Exposure gain slider
-    let (widget, exposure_gain_pos) = slider(&nvg,
+    let (widget, exposure_gain_pos, _) = slider(
         "Exposure gain".to_string(), "%".to_string(),
-        SliderPosition { minimum: 100., maximum: 500., step: 1., current: 0. });
-    exposure_time_pos.write(|slider|
+        "Amplification of the sensor signal; raises noise as well".to_string(),
+        SliderPosition { minimum: 100., maximum: 500., step: 1., current: 0. },
+        SliderScale::Linear);
+    exposure_gain_pos.write(|slider|
         slider.current = config.exposure_gain_pct().get() as f32);
     config.exposure_gain_pct().derive(exposure_gain_pos.clone(),
         |slider, value| SliderPosition { current: value as f32, ..*slider },
@@ -137,35 +420,453 @@ fn main() {
     cfg_layout.add(Box::new(widget));
 
     // Color temperature slider
-    let (widget, color_temp_pos) = slider(&nvg,
+    let (widget, color_temp_pos, color_temp_enabled) = slider(
         "Color temperature".to_string(), "K".to_string(),
-        SliderPosition { minimum: 2000., maximum: 15000., step: 10., current: 0. });
+        "White balance along the blue-yellow axis".to_string(),
+        SliderPosition { minimum: 2000., maximum: 15000., step: 10., current: 0. },
+        SliderScale::Linear);
     color_temp_pos.write(|slider|
         slider.current = config.color_temperature_k().get() as f32);
     config.color_temperature_k().derive(color_temp_pos.clone(),
         |slider, value| SliderPosition { current: value as f32, ..*slider },
         |slider|        slider.current as u32);
+    color_temp_enabled.link(color_supported.clone());
     cfg_layout.add(Box::new(widget));
 
     // Tint slider
-    let (widget, tint_pos) = slider(&nvg,
+    let (widget, tint_pos, tint_enabled) = slider(
         "Tint".to_string(), "".to_string(),
-        SliderPosition { minimum: 200., maximum: 2500., step: 10., current: 0. });
+        "White balance along the green-magenta axis".to_string(),
+        SliderPosition { minimum: 200., maximum: 2500., step: 10., current: 0. },
+        SliderScale::Linear);
     tint_pos.write(|slider|
         slider.current = config.tint().get() as f32);
     config.tint().derive(tint_pos.clone(),
         |slider, value| SliderPosition { current: value as f32, ..*slider },
         |slider|        slider.current as u32);
+    tint_enabled.link(color_supported.clone());
+    cfg_layout.add(Box::new(widget));
+
+    cfg_layout.add(Box::new(Separator::new(Direction::Horizontal)));
+
+    // Image processing sliders
+    let (widget, gamma_pos, _) = slider(
+        "Gamma".to_string(), "".to_string(),
+        "Brightness curve; higher values lift the shadows".to_string(),
+        SliderPosition { minimum: 20., maximum: 180., step: 1., current: 0. },
+        SliderScale::Linear);
+    gamma_pos.write(|slider|
+        slider.current = config.gamma().get() as f32);
+    config.gamma().derive(gamma_pos.clone(),
+        |slider, value| SliderPosition { current: value as f32, ..*slider },
+        |slider|        slider.current as u32);
+    cfg_layout.add(Box::new(widget));
+
+    let (widget, contrast_pos, _) = slider(
+        "Contrast".to_string(), "".to_string(),
+        "Difference between light and dark areas".to_string(),
+        SliderPosition { minimum: -100., maximum: 100., step: 1., current: 0. },
+        SliderScale::Linear);
+    contrast_pos.write(|slider|
+        slider.current = config.contrast().get() as f32);
+    config.contrast().derive(contrast_pos.clone(),
+        |slider, value| SliderPosition { current: value as f32, ..*slider },
+        |slider|        slider.current as i32);
+    cfg_layout.add(Box::new(widget));
+
+    let (widget, saturation_pos, saturation_enabled) = slider(
+        "Saturation".to_string(), "".to_string(),
+        "Intensity of colors; zero produces a grayscale image".to_string(),
+        SliderPosition { minimum: 0., maximum: 255., step: 1., current: 0. },
+        SliderScale::Linear);
+    saturation_pos.write(|slider|
+        slider.current = config.saturation().get() as f32);
+    config.saturation().derive(saturation_pos.clone(),
+        |slider, value| SliderPosition { current: value as f32, ..*slider },
+        |slider|        slider.current as u32);
+    saturation_enabled.link(color_supported.clone());
+    cfg_layout.add(Box::new(widget));
+
+    let (widget, hue_pos, hue_enabled) = slider(
+        "Hue".to_string(), "°".to_string(),
+        "Rotation of all colors around the color wheel".to_string(),
+        SliderPosition { minimum: -180., maximum: 180., step: 1., current: 0. },
+        SliderScale::Linear);
+    hue_pos.write(|slider|
+        slider.current = config.hue().get() as f32);
+    config.hue().derive(hue_pos.clone(),
+        |slider, value| SliderPosition { current: value as f32, ..*slider },
+        |slider|        slider.current as i32);
+    hue_enabled.link(color_supported.clone());
+    cfg_layout.add(Box::new(widget));
+
+    let (widget, sharpness_pos, _) = slider(
+        "Sharpness".to_string(), "".to_string(),
+        "Strength of edge enhancement".to_string(),
+        SliderPosition { minimum: 0., maximum: 500., step: 1., current: 0. },
+        SliderScale::Linear);
+    sharpness_pos.write(|slider|
+        slider.current = config.sharpness().get() as f32);
+    config.sharpness().derive(sharpness_pos.clone(),
+        |slider, value| SliderPosition { current: value as f32, ..*slider },
+        |slider|        slider.current as u32);
+    cfg_layout.add(Box::new(widget));
+
+    // Auto white balance button
+    let white_balance_button = Button::new("Auto white balance".to_string())
+        .with_tooltip("Adjust color temperature and tint to neutralize the scene".to_string());
+    white_balance_button.on_click(|| camera.auto_white_balance());
+    white_balance_button.enabled().link(color_supported.clone());
+    cfg_layout.add(Box::new(white_balance_button));
+
+    let white_balance_label = Label::new();
+    white_balance_label.text().link(config.color_temperature_k().zip(config.tint(),
+        |temperature, tint| format!("White balance: {} K / {}", temperature, tint)));
+    cfg_layout.add(Box::new(white_balance_label));
+
+    // White balance presets; "Custom" is only there while no preset matches
+    let white_balance_preset = Dropdown::new(vec![], 0)
+        .with_tooltip("Color temperature and tint for common kinds of lighting".to_string());
+    {
+        let (options, selected) =
+            (white_balance_preset.options(), white_balance_preset.selected());
+        config.color_temperature_k().zip(config.tint(), |&temperature, &tint|
+            WHITE_BALANCE_PRESETS.iter().position(|&(_, preset_temperature, preset_tint)|
+                temperature == preset_temperature && tint == preset_tint)
+        ).observe(move |preset| {
+            let mut names: Vec<String> = WHITE_BALANCE_PRESETS.iter()
+                .map(|&(name, _, _)| name.to_string()).collect();
+            if preset.is_none() { names.push("Custom".to_string()) }
+            if options.read(|options| *options != names) { options.set(names) }
+            selected.set(preset.unwrap_or(WHITE_BALANCE_PRESETS.len()))
+        });
+    }
+    // The preset is applied from the event loop, where the properties it changes are
+    // not in the middle of notifying the dropdown.
+    white_balance_preset.selected().notify(&event_tx, |index|
+        Event::WhiteBalancePreset(*index));
+    white_balance_preset.enabled().link(color_supported.clone());
+    cfg_layout.add(Box::new(white_balance_preset));
+
+    cfg_layout.add(Box::new(Separator::new(Direction::Horizontal)));
+
+    // Named presets of exposure and white balance
+    let preset_picker = Dropdown::new(config.preset_names(), 0);
+    let (preset_names, preset_selected) = (preset_picker.options(), preset_picker.selected());
+    cfg_layout.add(Box::new(preset_picker));
+
+    let preset_name_entry = TextEntry::new()
+        .with_tooltip("Name to save the current settings under".to_string());
+    let preset_name = preset_name_entry.text();
+    {
+        let (preset_names, preset_name) = (preset_names.clone(), preset_name.clone());
+        preset_selected.observe(move |&index| {
+            if let Some(name) = preset_names.read(|names| names.get(index).cloned()) {
+                preset_name.set(name)
+            }
+        });
+    }
+    cfg_layout.add(Box::new(preset_name_entry));
+    // Stray spaces typed around a name are not part of it.
+    let typed_preset_name = || preset_name.get().trim().to_string();
+
+    let mut preset_buttons = BoxLayout::horz();
+    preset_buttons.set_spacing(5.);
+    let save_preset = Button::new("Save".to_string())
+        .with_tooltip("Save exposure and white balance under the name above".to_string());
+    save_preset.on_click(|| event_tx.send(Event::SavePreset).unwrap());
+    preset_buttons.add(Box::new(save_preset));
+    let apply_preset = Button::new("Apply".to_string());
+    apply_preset.on_click(|| event_tx.send(Event::ApplyPreset).unwrap());
+    preset_buttons.add(Box::new(apply_preset));
+    let delete_preset = Button::new("Delete".to_string());
+    delete_preset.on_click(|| event_tx.send(Event::DeletePreset).unwrap());
+    preset_buttons.add(Box::new(delete_preset));
+    cfg_layout.add(Box::new(preset_buttons));
+
+    cfg_layout.add(Box::new(Separator::new(Direction::Horizontal)));
+
+    // Preview size dropdown
+    let preview_size = Dropdown::new(vec![], camera.preview_size().get());
+    let preview_sizes = preview_size.options();
+    let preview_size_selected = preview_size.selected();
+    preview_size.selected().propagate(camera.preview_size(), |x| *x);
+    cfg_layout.add(Box::new(preview_size));
+
+    // Full sensor button; a region of interest is selected by dragging over the image
+    let full_sensor = Button::new("Full sensor".to_string())
+        .with_tooltip("Capture the entire sensor again".to_string());
+    full_sensor.enabled().link(camera.roi().map(|roi| roi.is_some()));
+    full_sensor.on_click(|| camera.roi().set(None));
+    cfg_layout.add(Box::new(full_sensor));
+
+    // Binning and pixel format dropdowns
+    let binning = Dropdown::new(
+        (1..camera::MAX_BINNING + 1).map(|factor|
+            format!("{}×{} binning", factor, factor)).collect(),
+        camera.binning().get() as usize - 1)
+        .with_tooltip("Combine neighboring pixels for more sensitivity, but less detail"
+                      .to_string());
+    binning.selected().propagate(camera.binning(), |index| *index as u32 + 1);
+    binning.enabled().link(binning_supported);
+    cfg_layout.add(Box::new(binning));
+
+    let pixel_format = Dropdown::new(
+        camera::PixelFormat::all().iter().map(|format| format.name().to_string()).collect(),
+        camera::PixelFormat::all().iter().position(|format|
+            *format == camera.pixel_format().get()).unwrap());
+    pixel_format.selected().propagate(camera.pixel_format(), |index|
+        camera::PixelFormat::all()[*index]);
+    // A monochrome camera delivers gray pixels whatever the format.
+    pixel_format.enabled().link(color_supported.clone());
+    cfg_layout.add(Box::new(pixel_format));
+
+    // Trigger mode and button
+    let trigger_mode = RadioGroup::new(
+        camera::TriggerMode::all().iter().map(|mode| mode.name().to_string()).collect(),
+        Direction::Horizontal,
+        camera::TriggerMode::all().iter().position(|mode|
+            *mode == camera.trigger_mode().get()).unwrap())
+        .with_tooltip("When the camera takes a frame".to_string());
+    trigger_mode.selected().propagate(camera.trigger_mode(), |index|
+        camera::TriggerMode::all()[*index]);
+    trigger_mode.enabled().link(trigger_supported.clone());
+    cfg_layout.add(Box::new(trigger_mode));
+
+    let trigger_button = Button::new("Trigger".to_string())
+        .with_tooltip("Take a single frame in software trigger mode".to_string());
+    trigger_button.enabled().link(camera.trigger_mode().zip(trigger_supported,
+        |mode, &supported| supported && *mode == camera::TriggerMode::Software));
+    trigger_button.on_click(|| camera.trigger());
+    cfg_layout.add(Box::new(trigger_button));
+
+    // Histogram
+    let histogram = Histogram::new();
+    let histogram_bins = histogram.bins();
+    cfg_layout.add(Box::new(histogram));
+
+    // Live preview checkbox; the last frame stays on screen while it is cleared
+    let streaming = Checkbox::new(camera.streaming().get())
+        .with_tooltip("Snapshots can still be taken while the preview is paused".to_string());
+    streaming.text().set("Live preview".to_string());
+    streaming.value().propagate(camera.streaming(), |x| *x);
+    cfg_layout.add(Box::new(streaming));
+
+    // Status LED checkbox; only cameras that have one can switch it
+    let led = Checkbox::new(camera.led().get());
+    led.text().set("Status LED".to_string());
+    led.value().propagate(camera.led(), |x| *x);
+    cfg_layout.add(Box::new(led));
+
+    // Auto exposure checkbox
+    let auto_exposure = Checkbox::new(camera.auto_exposure().get());
+    auto_exposure.text().set("Auto exposure".to_string());
+    auto_exposure.value().propagate(camera.auto_exposure(), |x| *x);
+    cfg_layout.add(Box::new(auto_exposure));
+
+    // Auto exposure once button
+    let auto_exposure_once = Button::new("Auto exposure once".to_string())
+        .with_tooltip("Let the camera pick exposure time and gain, then keep them".to_string());
+    auto_exposure_once.on_click(|| camera.auto_exposure_once());
+    cfg_layout.add(Box::new(auto_exposure_once));
+
+    // Flip checkboxes
+    let hflip = Checkbox::new(camera.hflip().get());
+    hflip.text().set("Flip horizontally".to_string());
+    hflip.value().propagate(camera.hflip(), |x| *x);
+    cfg_layout.add(Box::new(hflip));
+
+    let vflip = Checkbox::new(camera.vflip().get());
+    vflip.text().set("Flip vertically".to_string());
+    vflip.value().propagate(camera.vflip(), |x| *x);
+    cfg_layout.add(Box::new(vflip));
+
+    // Rotate button; rotation is done when drawing, so it only affects the preview
+    let rotation = Property::new(0u8);
+    let rotate_button = Button::new("Rotate".to_string())
+        .with_tooltip("Rotate the preview clockwise by 90°".to_string());
+    {
+        let rotation = rotation.clone();
+        rotate_button.on_click(move || rotation.write(|value| *value = (*value + 1) % 4));
+    }
+    cfg_layout.add(Box::new(rotate_button));
+
+    // Crosshair checkbox
+    let (red, green, blue) = config.crosshair_color();
+    let crosshair = Crosshair::new(nanovg::Color::rgb_f(red, green, blue)).with_circle();
+    crosshair.shown().link(config.crosshair());
+    let show_crosshair = Checkbox::new(config.crosshair().get())
+        .with_tooltip("Mark the center of the image for alignment".to_string());
+    show_crosshair.text().set("Crosshair".to_string());
+    show_crosshair.value().propagate(config.crosshair(), |x| *x);
+    cfg_layout.add(Box::new(show_crosshair));
+
+    // Grid checkboxes and pitch slider
+    let thirds = Grid::new(nanovg::Color::rgb_f(red, green, blue), GridSpacing::Divisions(3));
+    thirds.shown().link(config.thirds());
+    let show_thirds = Checkbox::new(config.thirds().get())
+        .with_tooltip("Divide the image into thirds for composition".to_string());
+    show_thirds.text().set("Rule of thirds".to_string());
+    show_thirds.value().propagate(config.thirds(), |x| *x);
+    cfg_layout.add(Box::new(show_thirds));
+
+    let grid = Grid::new(nanovg::Color::rgb_f(red, green, blue),
+                         GridSpacing::Pixels(config.grid_pitch_px().get() as f32));
+    grid.shown().link(config.grid());
+    let show_grid = Checkbox::new(config.grid().get())
+        .with_tooltip("Lines at a fixed distance in image pixels".to_string());
+    show_grid.text().set("Grid".to_string());
+    show_grid.value().propagate(config.grid(), |x| *x);
+    cfg_layout.add(Box::new(show_grid));
+
+    let (widget, grid_pitch_pos, _) = slider(
+        "Grid pitch".to_string(), "px".to_string(),
+        "Distance between grid lines, in image pixels".to_string(),
+        SliderPosition { minimum: 10., maximum: 1000., step: 10., current: 0. },
+        SliderScale::Logarithmic);
+    grid_pitch_pos.write(|slider|
+        slider.current = config.grid_pitch_px().get() as f32);
+    config.grid_pitch_px().derive(grid_pitch_pos.clone(),
+        |slider, value| SliderPosition { current: value as f32, ..*slider },
+        |slider|        slider.current as u32);
+    config.grid_pitch_px().propagate(grid.spacing(), |pitch| GridSpacing::Pixels(*pitch as f32));
     cfg_layout.add(Box::new(widget));
 
-    let mut cfg_frame = Frame::new(&nvg, Box::new(cfg_layout));
+    // Still image bit depth checkbox
+    let high_bit_depth = Checkbox::new(camera.still_bit_depth().get() > 8)
+        .with_tooltip("Keep full sensor precision in snapshots".to_string());
+    high_bit_depth.text().set("16-bit snapshots".to_string());
+    high_bit_depth.value().propagate(camera.still_bit_depth(), |x| if *x { 16 } else { 8 });
+    cfg_layout.add(Box::new(high_bit_depth));
+
+    cfg_layout.add(Box::new(Separator::new(Direction::Horizontal)));
+
+    // Sensor status label
+    let telemetry = Property::new(None);
+    let telemetry_label = Label::new();
+    telemetry_label.text().link(telemetry.map(|value| match *value {
+        Some((temperature_c, frame_count)) =>
+            format!("Sensor: {:.1} °C, {} frames", temperature_c, frame_count),
+        None => "Sensor: no camera".to_string()
+    }));
+    cfg_layout.add(Box::new(telemetry_label));
+
+    // Recording checkbox and indicator
+    let recorder = recorder::Recorder::new();
+    let record = Checkbox::new(false)
+        .with_tooltip("Save every preview frame until unchecked".to_string());
+    record.text().set("Record".to_string());
+    record.value().notify(&event_tx, |value| Event::Record(*value));
+    let recording = record.value();
+    cfg_layout.add(Box::new(record));
+
+    let recording_label = Label::new();
+    let recording_status = recording_label.text();
+    cfg_layout.add(Box::new(recording_label));
+
+    // Dark frame button and checkbox
+    let capture_dark = Button::new("Capture dark frame".to_string())
+        .with_tooltip("Cover the lens first; the next frames are averaged".to_string());
+    capture_dark.on_click(|| event_tx.send(Event::CaptureDarkFrame).unwrap());
+    cfg_layout.add(Box::new(capture_dark));
+
+    let subtract_dark = Checkbox::new(false)
+        .with_tooltip("Remove the sensor's fixed pattern noise and hot pixels".to_string());
+    subtract_dark.text().set("Subtract dark frame".to_string());
+    let subtract_dark_frame = subtract_dark.value();
+    cfg_layout.add(Box::new(subtract_dark));
+    // Frames being averaged into a dark frame, and the last dark frame captured.
+    let mut dark_frames: Option<processing::Accumulator> = None;
+    let mut dark_frame: Option<processing::DarkFrame> = None;
+
+    // Frame averaging slider
+    let (widget, averaged_frames_pos, _) = slider(
+        "Averaging".to_string(), "frames".to_string(),
+        "Averages the last frames to reduce noise; 1 turns it off".to_string(),
+        SliderPosition { minimum: 1., maximum: processing::MAX_AVERAGED_FRAMES as f32,
+                         step: 1., current: 1. },
+        SliderScale::Linear);
+    cfg_layout.add(Box::new(widget));
+    let mut running_average = processing::RunningAverage::new();
+
+    // Theme checkbox
+    let light_theme = Checkbox::new(false);
+    light_theme.text().set("Light theme".to_string());
+    light_theme.value().notify(&event_tx, |value|
+        Event::SetTheme(if *value { Theme::Light } else { Theme::Dark }));
+    cfg_layout.add(Box::new(light_theme));
+
+    // Snap button
+    let snap_button = Button::new("Snap".to_string());
+    let snap_spinner = Spinner::new();
+    // Ids of the snapshots that were requested but not yet saved.
+    let pending_snaps = Property::new(Vec::new());
+    pending_snaps.propagate(snap_spinner.active(), |ids| !ids.is_empty());
+    {
+        let (pending_snaps, camera) = (pending_snaps.clone(), &camera);
+        snap_button.on_click(move || {
+            let id = camera.snap();
+            pending_snaps.write(|ids| ids.push(id))
+        });
+    }
+
+    let mut snap_layout = BoxLayout::horz();
+    snap_layout.set_spacing(10.);
+    snap_layout.set_alignment(Alignment::Center);
+    snap_layout.add(Box::new(snap_button));
+    snap_layout.add(Box::new(snap_spinner));
+    cfg_layout.add(Box::new(snap_layout));
+
+    // FPS readout
+    let frame_rate = Property::new(None);
+    let frame_rate_label = Label::new();
+    frame_rate_label.text().link(frame_rate.map(|value| match *value {
+        Some(frame_rate) => format!("Camera: {:.1} FPS", frame_rate),
+        None => "Camera: no stream".to_string()
+    }));
+    cfg_layout.add(Box::new(frame_rate_label));
+
+    let fps_label = Label::new();
+    let fps_text = fps_label.text();
+    cfg_layout.add(Box::new(fps_label));
+
+    let mut cfg_scroll = ScrollView::new(Box::new(cfg_layout));
+    cfg_scroll.set_max_height(900.);
+
+    let mut cfg_frame = Frame::new(Box::new(cfg_scroll));
     cfg_frame.set_position(Point(20.0, 20.0));
+    cfg_frame.set_title("Camera".to_string());
 
     let mut ui = Overlay::new(&nvg);
-    ui.background.from_png(png::load_png_from_memory(include_bytes!("../res/nosignal.png")).unwrap());
+    ui.set_debug(env::var_os("PSYCHIC_SEAMSTRESS_DEBUG_UI").is_some());
+    load_idle_background(&ui.background, &config);
     ui.frames.push(cfg_frame);
+    ui.guides.push(Box::new(crosshair));
+    ui.guides.push(Box::new(thirds));
+    ui.guides.push(Box::new(grid));
+    ui.set_calibration(config.pixels_per_mm());
+    if let Some(err) = config_error {
+        ui.notify(format!("Cannot load settings, using defaults: {}", err))
+    }
+
+    // Region of interest; a selection made on an already cropped image refines it
+    {
+        let roi = camera.roi();
+        ui.roi().observe(move |selection| {
+            let (x, y) = roi.get().map_or((0, 0), |roi| (roi.x, roi.y));
+            roi.set(selection.map(|Rect(Point(dx, dy), Point(width, height))| camera::Roi {
+                x: x + dx as u32, y: y + dy as u32, width: width as u32, height: height as u32
+            }))
+        });
+    }
 
     let mut camera_connected = false;
+    // Cleared when the user disconnects the camera, so that hotplug does not reconnect it.
+    let mut auto_connect = screenshot_path.is_none();
+    // The camera to connect to when it appears; any camera if none was picked.
+    let mut preferred_camera = args.camera.clone();
+    let mut exiting = false;
+    let (mut last_frame_at, mut fps_updated_at, mut frame_time) = (glfw.get_time(), 0., 0.);
     while !window.should_close() {
         // Check if window was resized
         let (win_width, win_height) = window.get_size();
@@ -173,43 +874,272 @@ fn main() {
         let pixel_ratio = fb_width as f32 / win_width as f32;
 
         // Reflow UI
-        ui.prepare();
+        let now = glfw.get_time();
+        ui.set_scale(pixel_ratio * config.ui_scale());
+        ui.set_frame_alpha(config.frame_alpha());
+        ui.prepare(now);
+
+        // A frame that is due before the limit allows it waits for the limit.
+        let frame_pending = ui.needs_redraw();
+        let frame_allowed = now - last_frame_at >= frame_interval;
+        if frame_pending && frame_allowed {
+            // Measure frame rate; only frames that are drawn count
+            frame_time += (now - last_frame_at - frame_time) * FPS_SMOOTHING;
+            last_frame_at = now;
+            if now - fps_updated_at > FPS_UPDATE_INTERVAL && frame_time > 0. {
+                fps_text.set(format!("UI: {:.1} FPS", 1. / frame_time));
+                fps_updated_at = now
+            }
 
-        // Render UI
-        gl!(Viewport(0, 0, fb_width, fb_height));
-        gl!(ClearColor(0.0, 0.0, 0.0, 0.0));
-        gl!(Clear(COLOR_BUFFER_BIT | DEPTH_BUFFER_BIT | STENCIL_BUFFER_BIT));
+            // Render UI
+            gl!(Viewport(0, 0, fb_width, fb_height));
+            gl!(ClearColor(0.0, 0.0, 0.0, 0.0));
+            gl!(Clear(COLOR_BUFFER_BIT | DEPTH_BUFFER_BIT | STENCIL_BUFFER_BIT));
 
-        nvg.begin_frame(win_width as u32, win_height as u32, pixel_ratio);
-        ui.draw(Point(fb_width as f32, fb_height as f32));
-        nvg.end_frame();
+            nvg.begin_frame(win_width as u32, win_height as u32, pixel_ratio);
+            ui.draw(Point(fb_width as f32, fb_height as f32));
+            nvg.end_frame();
 
-        window.swap_buffers();
+            if let Some(ref path) = screenshot_path {
+                if let Err(err) = save_screenshot(fb_width, fb_height, path) {
+                    let _ = writeln!(io::stderr(), "Cannot save screenshot: {}", err);
+                    process::exit(1)
+                }
+                return
+            }
+
+            window.swap_buffers();
+        }
 
-        // Handle events
+        // Handle events; every one of them may change what is shown
         for event in glfw::flush_messages(&event_rx) {
+            ui.invalidate();
             match event {
-                Event::Camera(camera::Event::Hotplug(_)) => {
-                    if !camera_connected { camera.connect(None) }
+                Event::Camera(camera::Event::Hotplug(instances)) => {
+                    device_names.set(instances.iter().map(|instance|
+                        instance.display_name.clone()).collect());
+                    // Devices may be listed in another order now; keep the same one selected.
+                    let preferred_index = preferred_camera.as_ref().and_then(|id|
+                        instances.iter().position(|instance| instance.unique_id == *id));
+                    devices = instances;
+                    if let Some(index) = preferred_index {
+                        if device_selected.get() != index { device_selected.set(index) }
+                    }
+                    if !camera_connected && auto_connect {
+                        camera.connect(preferred_camera.clone())
+                    }
+                }
+                Event::SelectDevice(index) => {
+                    if let Some(instance) = devices.get(index) {
+                        // Selecting the camera that is connected, or is connected to as soon
+                        // as it appears, must not connect to it once more.
+                        if (camera_connected || auto_connect) &&
+                                preferred_camera.as_ref() == Some(&instance.unique_id) {
+                            continue
+                        }
+                        auto_connect = true;
+                        preferred_camera = Some(instance.unique_id.clone());
+                        camera.connect(preferred_camera.clone())
+                    }
+                }
+                Event::WhiteBalancePreset(index) => {
+                    if let Some(&(_, temperature, tint)) = WHITE_BALANCE_PRESETS.get(index) {
+                        // Selecting the preset that already matches must not loop back.
+                        if config.color_temperature_k().get() != temperature ||
+                                config.tint().get() != tint {
+                            property::batch(|batch| {
+                                batch.set(&config.color_temperature_k(), temperature);
+                                batch.set(&config.tint(), tint)
+                            })
+                        }
+                    }
+                }
+                Event::SavePreset => {
+                    let name = typed_preset_name();
+                    if name.is_empty() {
+                        ui.notify("Cannot save preset: no name given".to_string())
+                    } else {
+                        if config.save_preset(&name) {
+                            ui.notify(format!("Replaced preset \"{}\"", name))
+                        }
+                        let names = config.preset_names();
+                        let index = names.iter().position(|other| *other == name).unwrap();
+                        preset_names.set(names);
+                        preset_selected.set(index);
+                        store_config(&config, config_path)
+                    }
+                }
+                Event::ApplyPreset => {
+                    let name = typed_preset_name();
+                    if !config.apply_preset(&name) {
+                        ui.notify(format!("No preset called \"{}\"", name))
+                    }
+                }
+                Event::DeletePreset => {
+                    let name = typed_preset_name();
+                    if config.delete_preset(&name) {
+                        let names = config.preset_names();
+                        let count = names.len();
+                        preset_names.set(names);
+                        // Select the preset that took the place of the deleted one, if any.
+                        if count > 0 {
+                            preset_selected.set(cmp::min(preset_selected.get(), count - 1))
+                        } else {
+                            preset_name.set(String::new())
+                        }
+                        store_config(&config, config_path)
+                    } else {
+                        ui.notify(format!("No preset called \"{}\"", name))
+                    }
+                }
+                Event::Record(true) => {
+                    match recorder.start(&config.snapshot_dir()) {
+                        Ok(path) => ui.notify(format!("Recording to {}", path.display())),
+                        Err(err) => {
+                            let message = format!("Cannot record: {}", err);
+                            let _ = writeln!(io::stderr(), "{}", message);
+                            ui.notify(message)
+                        }
+                    }
+                }
+                Event::CaptureDarkFrame => {
+                    dark_frames = Some(processing::Accumulator::new())
+                }
+                Event::Record(false) => {
+                    recorder.stop();
+                    recording_status.set("".to_string())
+                }
+                Event::SetTheme(theme) => {
+                    ui.set_theme(theme)
+                }
+                Event::Disconnect => {
+                    auto_connect = false;
+                    camera.disconnect()
                 }
                 Event::Camera(camera::Event::Connect) => {
                     camera_connected = true;
                 }
-                Event::Camera(camera::Event::Image(image)) => {
-                    ui.background.from_touptek(image);
+                Event::Camera(camera::Event::Reconnected) => {
+                    camera_connected = true;
+                    camera.resend_settings();
+                    ui.notify("Camera reconnected".to_string())
+                }
+                Event::Camera(camera::Event::ExposureRange { min_us, max_us }) => {
+                    // The validator brings the current exposure time into the new range.
+                    exposure_time_pos.write(|slider| {
+                        slider.minimum = exposure_us_to_ms(min_us);
+                        slider.maximum = exposure_us_to_ms(max_us);
+                    })
                 }
-                Event::Camera(camera::Event::StillImage(touptek::Image {
-                    resolution: touptek::Resolution { width, height }, data, ..
-                })) => {
-                    let mut image = png::Image {
-                        width: width, height: height,
-                        pixels: png::PixelsByColorType::RGBA8(data)
+                Event::Camera(camera::Event::Capabilities(new_capabilities)) => {
+                    capabilities.set(Some(new_capabilities))
+                }
+                Event::Camera(camera::Event::PreviewSizes(resolutions)) => {
+                    // The selection may have been made for a camera with more sizes.
+                    let last = resolutions.len().saturating_sub(1);
+                    if preview_size_selected.get() > last { preview_size_selected.set(last) }
+                    preview_sizes.set(resolutions.iter().map(|resolution|
+                        format!("{}x{}", resolution.width, resolution.height)).collect())
+                }
+                Event::Camera(camera::Event::Image) => {
+                    let mut image = match camera.take_frame() {
+                        Some(image) => image,
+                        None => continue
                     };
-                    png::store_png(&mut image, Path::new("/tmp/foo.png")).unwrap()
+
+                    let captured = match dark_frames {
+                        Some(ref mut frames) => {
+                            frames.add(&image);
+                            frames.count() == processing::DARK_FRAME_COUNT
+                        }
+                        None => false
+                    };
+                    if captured {
+                        dark_frame = dark_frames.take().as_ref()
+                                                .map(processing::DarkFrame::from_average);
+                        ui.notify("Dark frame captured".to_string())
+                    }
+                    let stale = dark_frame.as_ref()
+                                          .map_or(false, |dark_frame| !dark_frame.matches(&image));
+                    if stale {
+                        // The resolution changed, and the dark frame is of no use anymore.
+                        dark_frame = None;
+                        ui.notify("Dark frame discarded; capture it again".to_string())
+                    }
+                    if subtract_dark_frame.get() {
+                        if let Some(ref dark_frame) = dark_frame {
+                            dark_frame.subtract_from(&mut image)
+                        }
+                    }
+
+                    if recorder.is_recording() {
+                        match recorder.record(&image) {
+                            Ok(()) =>
+                                recording_status.set(format!(
+                                    "● Recording: {} frames, {} dropped",
+                                    recorder.recorded_frames(), recorder.dropped_frames())),
+                            Err(err) => {
+                                let message = format!("Recording stopped: {}", err);
+                                let _ = writeln!(io::stderr(), "{}", message);
+                                ui.notify(message);
+                                recording_status.set("".to_string());
+                                recording.set(false)
+                            }
+                        }
+                    }
+                    // Only the displayed image is averaged; recordings get every frame.
+                    running_average.set_length(averaged_frames_pos.get().current as u32);
+                    running_average.apply(&mut image);
+                    histogram_bins.set(HistogramBins::compute(&image.data, HISTOGRAM_STRIDE));
+                    ui.background.set_orientation(false, false, rotation.get());
+                    ui.background.from_touptek(image);
+                }
+                Event::Camera(camera::Event::StillImage { id, image }) => {
+                    pending_snaps.write(|ids| ids.retain(|&pending_id| pending_id != id));
+                    match camera::save_still_image(image, &config.snapshot_dir()) {
+                        Ok(path) =>
+                            ui.notify(format!("Saved snapshot #{} to {}", id, path.display())),
+                        Err(err) => {
+                            let message = format!("Cannot save snapshot #{}: {}", id, err);
+                            let _ = writeln!(io::stderr(), "{}", message);
+                            ui.notify(message)
+                        }
+                    }
+                }
+                Event::Camera(camera::Event::WhiteBalance { temperature, tint }) => {
+                    property::batch(|batch| {
+                        batch.set(&config.color_temperature_k(), temperature);
+                        batch.set(&config.tint(), tint)
+                    })
+                }
+                Event::Camera(camera::Event::Exposure { time_us, gain_pct }) => {
+                    property::batch(|batch| {
+                        batch.set(&config.exposure_time_us(), time_us);
+                        batch.set(&config.exposure_gain_pct(), gain_pct)
+                    })
+                }
+                Event::Camera(camera::Event::Error(message)) => {
+                    let _ = writeln!(io::stderr(), "{}", message);
+                    ui.notify(message)
+                }
+                Event::Camera(camera::Event::Reconnecting { attempt }) => {
+                    ui.notify(format!("Camera lost, reconnecting (attempt {})", attempt))
+                }
+                Event::Camera(camera::Event::Telemetry { temperature_c, frame_count }) => {
+                    telemetry.set(Some((temperature_c, frame_count)))
+                }
+                Event::Camera(camera::Event::FrameRate(value)) => {
+                    frame_rate.set(Some(value))
                 }
                 Event::Camera(camera::Event::Disconnect) => {
                     camera_connected = false;
-                    ui.background.from_png(png::load_png("res/nosignal.png").unwrap())
+                    if exiting { return }
+                    pending_snaps.set(Vec::new());
+                    telemetry.set(None);
+                    frame_rate.set(None);
+                    capabilities.set(None);
+                    ui.background.set_orientation(false, false, 0);
+                    load_idle_background(&ui.background, &config)
                 }
                 Event::Glfw(event) => {
                     use glfw::*;
@@ -217,25 +1147,120 @@ fn main() {
                     match event {
                         WindowEvent::CursorPos(x, y) =>
                             ui.mouse_move(Point(x as f32, y as f32) * pixel_ratio),
-                        WindowEvent::MouseButton(_button, Action::Press, _modifiers) =>
-                            ui.mouse_down(),
+                        WindowEvent::MouseButton(MouseButton::Button3, Action::Press, _modifiers) =>
+                            ui.pan_begin(),
+                        WindowEvent::MouseButton(MouseButton::Button3, Action::Release, _modifiers) =>
+                            ui.pan_end(),
+                        WindowEvent::MouseButton(MouseButton::Button2, Action::Press, _modifiers) =>
+                            ui.mouse_down_right(),
+                        WindowEvent::MouseButton(MouseButton::Button2, Action::Release, _modifiers) =>
+                            (),
+                        WindowEvent::MouseButton(_button, Action::Press, _modifiers) => {
+                            ui.mouse_down();
+                            if let Some(text) = ui.take_clipboard() {
+                                window.set_clipboard_string(&text)
+                            }
+                        }
                         WindowEvent::MouseButton(_button, Action::Release, _modifiers) =>
                             ui.mouse_up(),
                         WindowEvent::Scroll(x, y) =>
                             ui.mouse_scroll(Point(x as f32, y as f32)),
-                        WindowEvent::Key(Key::Space, _, Action::Press, _modifiers) =>
-                            camera.snap(),
                         WindowEvent::Key(Key::Escape, _, Action::Press, _modifiers) => {
-                            config::store(&config);
-                            return
+                            if !config.fullscreen() {
+                                store_window_geometry(&mut config, &window)
+                            }
+                            store_config(&config, config_path);
+                            // Let the camera thread close the camera before exiting.
+                            if !camera_connected { return }
+                            camera.disconnect();
+                            exiting = true
+                        }
+                        WindowEvent::Key(key, _, Action::Press, modifiers)
+                                if modifiers.contains(Control) &&
+                                   (key == Key::Equal || key == Key::Minus || key == Key::Num0) => {
+                            let scale = match key {
+                                Key::Equal => config.ui_scale() * UI_SCALE_STEP,
+                                Key::Minus => config.ui_scale() / UI_SCALE_STEP,
+                                _ => 1.
+                            };
+                            config.set_ui_scale(scale.max(UI_SCALE_MIN).min(UI_SCALE_MAX))
+                        }
+                        WindowEvent::Key(Key::F1, _, Action::Press, _modifiers) =>
+                            ui.set_visible(!ui.visible()),
+                        WindowEvent::Key(Key::F2, _, Action::Press, _modifiers) =>
+                            ui.set_picture_in_picture(!ui.picture_in_picture()),
+                        WindowEvent::Key(Key::F3, _, Action::Press, _modifiers) => {
+                            let measuring = !ui.measuring();
+                            ui.set_measuring(measuring);
+                            if measuring {
+                                ui.notify("Click two points to measure the distance between \
+                                           them".to_string())
+                            }
+                        }
+                        WindowEvent::Key(Key::F4, _, Action::Press, _modifiers) =>
+                            ui.set_inspecting(!ui.inspecting()),
+                        WindowEvent::Key(Key::F11, _, Action::Press, _modifiers) => {
+                            let fullscreen = !config.fullscreen();
+                            set_fullscreen(&mut glfw, &mut window, &mut config, fullscreen)
                         }
+                        WindowEvent::Key(key, _, Action::Press, modifiers)
+                                if modifiers.contains(Control) &&
+                                   (key == Key::LeftBracket || key == Key::RightBracket) => {
+                            let alpha = match key {
+                                Key::RightBracket => config.frame_alpha() + FRAME_ALPHA_STEP,
+                                _ => config.frame_alpha() - FRAME_ALPHA_STEP
+                            };
+                            // Fully transparent controls could not be found again.
+                            config.set_frame_alpha(alpha.max(FRAME_ALPHA_STEP).min(1.))
+                        }
+                        WindowEvent::Key(Key::Tab, _, Action::Press, modifiers) => {
+                            if modifiers.contains(Shift) {
+                                ui.focus_prev()
+                            } else {
+                                ui.focus_next()
+                            }
+                        }
+                        WindowEvent::Char(character) =>
+                            ui.char_input(character),
+                        WindowEvent::Key(key, _, Action::Press, modifiers) |
+                        WindowEvent::Key(key, _, Action::Repeat, modifiers) =>
+                            ui.key_input(key, modifiers),
                         _ => {}
                     }
                 }
             }
         }
 
-        // Poke GLFW
-        glfw.poll_events();
+        // Sleep until GLFW has events; camera events do not wake it up, so check on them
+        // every now and then. A frame held back by the limit is drawn as soon as it allows.
+        let timeout = if frame_pending && !frame_allowed {
+            (last_frame_at + frame_interval - glfw.get_time()).max(0.).min(EVENT_WAIT_TIMEOUT)
+        } else {
+            EVENT_WAIT_TIMEOUT
+        };
+        glfw.wait_events_timeout(timeout);
+    }
+
+    if !config.fullscreen() {
+        store_window_geometry(&mut config, &window)
+    }
+    store_config(&config, config_path);
+}
+
+#[cfg(test)]
+mod tests {
+    use ui::SliderPosition;
+    use super::{exposure_us_to_ms, exposure_ms_to_us, EXPOSURE_STEP_MS};
+
+    #[test]
+    fn exposure_time_survives_slider() {
+        for &(min_us, time_us) in &[(244, 120000), (244, 245), (15, 37), (15, 1999999)] {
+            let mut position = SliderPosition {
+                minimum: exposure_us_to_ms(min_us), maximum: 2000., step: EXPOSURE_STEP_MS,
+                current: exposure_us_to_ms(time_us)
+            };
+            position.validator();
+            assert_eq!(exposure_ms_to_us(position.current), time_us);
+        }
     }
 }
@@ -0,0 +1,189 @@
+extern crate simd;
+extern crate touptek;
+
+use std::cmp;
+use std::collections::VecDeque;
+
+// How many frames are averaged into a dark frame, to keep its own noise low.
+pub const DARK_FRAME_COUNT: u32 = 16;
+
+/// Most frames that can be averaged for display.
+pub const MAX_AVERAGED_FRAMES: u32 = 16;
+// Most memory the frames kept for averaging may take up; at high resolutions, fewer
+// frames than requested are averaged.
+const MAX_AVERAGED_BYTES: usize = 256 << 20;
+
+/// Sums up RGBA8 frames pixel by pixel, to average them.
+pub struct Accumulator {
+    resolution: (u32, u32),
+    sums: Vec<u32>,
+    count: u32,
+}
+
+impl Accumulator {
+    pub fn new() -> Accumulator {
+        Accumulator {
+            resolution: (0, 0),
+            sums: Vec::new(),
+            count: 0,
+        }
+    }
+
+    pub fn count(&self) -> u32 { self.count }
+
+    pub fn clear(&mut self) {
+        self.sums.clear();
+        self.count = 0
+    }
+
+    /// Whether `image` can be added without starting the sum anew.
+    pub fn matches(&self, image: &touptek::Image) -> bool {
+        let touptek::Resolution { width, height } = image.resolution;
+        (width, height) == self.resolution && self.sums.len() == image.data.len()
+    }
+
+    /// Adds a frame to the sum; a frame of another size than the ones before it starts
+    /// the sum anew.
+    pub fn add(&mut self, image: &touptek::Image) {
+        if !self.matches(image) {
+            let touptek::Resolution { width, height } = image.resolution;
+            self.resolution = (width, height);
+            self.sums = vec![0; image.data.len()];
+            self.count = 0
+        }
+
+        for (sum, &value) in self.sums.iter_mut().zip(image.data.iter()) {
+            *sum += value as u32
+        }
+        self.count += 1
+    }
+
+    /// Takes a frame that was added earlier back out of the sum.
+    pub fn remove(&mut self, data: &[u8]) {
+        for (sum, &value) in self.sums.iter_mut().zip(data.iter()) {
+            *sum -= value as u32
+        }
+        self.count -= 1
+    }
+
+    /// Returns the average of the frames added so far, with the same layout as they had.
+    pub fn average(&self) -> Vec<u8> {
+        let count = if self.count == 0 { 1 } else { self.count };
+        self.sums.iter().map(|&sum| ((sum + count / 2) / count) as u8).collect()
+    }
+}
+
+/// Averages every frame with the ones that came right before it, to reduce noise
+/// in the displayed image.
+pub struct RunningAverage {
+    length: u32,
+    frames: VecDeque<Vec<u8>>,
+    sum: Accumulator,
+}
+
+impl RunningAverage {
+    pub fn new() -> RunningAverage {
+        RunningAverage {
+            length: 1,
+            frames: VecDeque::new(),
+            sum: Accumulator::new(),
+        }
+    }
+
+    /// Sets how many frames are averaged, up to `MAX_AVERAGED_FRAMES`; 1 turns
+    /// averaging off.
+    pub fn set_length(&mut self, length: u32) {
+        self.length = cmp::max(1, cmp::min(length, MAX_AVERAGED_FRAMES))
+    }
+
+    pub fn clear(&mut self) {
+        self.frames.clear();
+        self.sum.clear()
+    }
+
+    /// Replaces `image` with the average of it and the frames before it.
+    pub fn apply(&mut self, image: &mut touptek::Image) {
+        let frame_bytes = cmp::max(1, image.data.len());
+        let length = cmp::min(self.length as usize, MAX_AVERAGED_BYTES / frame_bytes);
+        if length <= 1 {
+            self.clear();
+            return
+        }
+
+        if !self.sum.matches(image) {
+            // Frames of another resolution cannot be averaged with this one.
+            self.clear()
+        }
+        self.sum.add(image);
+        self.frames.push_back(image.data.clone());
+        while self.frames.len() > length {
+            let frame = self.frames.pop_front().unwrap();
+            self.sum.remove(&frame)
+        }
+        image.data = self.sum.average()
+    }
+}
+
+/// The image the sensor produces with no light on it, i.e. its fixed pattern noise and
+/// hot pixels, to be subtracted from further frames.
+pub struct DarkFrame {
+    resolution: (u32, u32),
+    pixels: Vec<u8>,
+}
+
+impl DarkFrame {
+    pub fn from_average(frames: &Accumulator) -> DarkFrame {
+        let mut pixels = frames.average();
+        // The alpha channel must stay as it is after subtraction.
+        for pixel in pixels.chunks_mut(4) {
+            if pixel.len() == 4 { pixel[3] = 0 }
+        }
+        DarkFrame { resolution: frames.resolution, pixels: pixels }
+    }
+
+    /// Whether the dark frame was taken at the resolution of `image`.
+    pub fn matches(&self, image: &touptek::Image) -> bool {
+        let touptek::Resolution { width, height } = image.resolution;
+        (width, height) == self.resolution && image.data.len() == self.pixels.len()
+    }
+
+    /// Subtracts the dark frame from `image`, if it `matches`; dark pixels become zero,
+    /// rather than wrapping around.
+    pub fn subtract_from(&self, image: &mut touptek::Image) {
+        if self.matches(image) {
+            subtract_saturating(&mut image.data, &self.pixels)
+        }
+    }
+}
+
+fn subtract_saturating(data: &mut [u8], subtrahend: &[u8]) {
+    let zero_x16 = simd::u8x16::splat(0);
+    let mut index = 0;
+    let length = data.len();
+    while index + 16 <= length {
+        let (value, other) = (simd::u8x16::load(data, index),
+                              simd::u8x16::load(subtrahend, index));
+        value.ge(other).select(value - other, zero_x16).store(data, index);
+        index += 16
+    }
+
+    for (value, &other) in data[index..].iter_mut().zip(subtrahend[index..].iter()) {
+        *value = value.saturating_sub(other)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::subtract_saturating;
+
+    #[test]
+    fn subtraction_saturates_in_vectors_and_tail() {
+        // 16 bytes go through the vectorized loop, and the last 3 through the scalar one.
+        let mut data = (0..19).map(|value| value * 10).collect::<Vec<u8>>();
+        let mut subtrahend = vec![25; 16];
+        subtrahend.extend_from_slice(&[150, 175, 200]);
+        subtract_saturating(&mut data, &subtrahend);
+        assert_eq!(data, vec![0, 0, 0, 5, 15, 25, 35, 45, 55, 65, 75, 85, 95, 105, 115, 125,
+                              10, 0, 0]);
+    }
+}
@@ -1,5 +1,7 @@
-use std::cell::RefCell;
+use std::cell::{BorrowState, Cell, RefCell};
+use std::io::{self, Write};
 use std::rc::Rc;
+use std::sync::{Arc, Mutex};
 use std::sync::mpsc::{channel, Sender};
 use std::mem;
 
@@ -7,15 +9,22 @@ use serde;
 
 trait Observable<T> {
     fn read(&self, reader: &mut FnMut(&T));
-    fn write(&mut self, writer: &mut FnMut(&mut T));
-    fn observe(&mut self, observer: Box<FnMut(&T) + 'static>);
-    fn destruct(&mut self) -> Vec<Box<FnMut(&T)>>;
+    // Changes the value without letting the observers know; see `announce`.
+    fn change(&mut self, writer: &mut FnMut(&mut T));
+    fn announce(&mut self);
+    // Identifies the root property that holds the value.
+    fn key(&self) -> usize;
+    fn observe(&mut self, id: usize, observer: Box<FnMut(&T) + 'static>);
+    fn unobserve(&mut self, id: usize);
+    fn destruct(&mut self) -> Vec<(usize, Box<FnMut(&T)>)>;
+    // Whether a property that this one reads from or writes to is being changed.
+    fn busy(&self) -> bool { false }
 }
 
 struct Root<T> {
     value:     T,
     validator: Box<FnMut(&mut T)>,
-    observers: Vec<Box<FnMut(&T)>>
+    observers: Vec<(usize, Box<FnMut(&T)>)>
 }
 
 impl<T> Root<T> where T: 'static {
@@ -34,69 +43,122 @@ impl<T> Observable<T> for Root<T> {
         reader(&self.value)
     }
 
-    fn write(&mut self, writer: &mut FnMut(&mut T)) {
+    fn change(&mut self, writer: &mut FnMut(&mut T)) {
         writer(&mut self.value);
-        (*self.validator)(&mut self.value);
-        for observer in self.observers.iter_mut() {
+        (*self.validator)(&mut self.value)
+    }
+
+    fn announce(&mut self) {
+        for &mut (_, ref mut observer) in self.observers.iter_mut() {
             (*observer)(&self.value)
         }
     }
 
-    fn observe(&mut self, mut observer: Box<FnMut(&T) + 'static>) {
+    fn key(&self) -> usize {
+        self as *const Root<T> as usize
+    }
+
+    fn observe(&mut self, id: usize, mut observer: Box<FnMut(&T) + 'static>) {
         observer(&self.value);
-        self.observers.push(observer)
+        self.observers.push((id, observer))
     }
 
-    fn destruct(&mut self) -> Vec<Box<FnMut(&T)>> {
+    fn unobserve(&mut self, id: usize) {
+        self.observers.retain(|&(observer_id, _)| observer_id != id)
+    }
+
+    fn destruct(&mut self) -> Vec<(usize, Box<FnMut(&T)>)> {
         let mut observers = Vec::new();
         mem::swap(&mut self.observers, &mut observers);
         observers
     }
 }
 
+// Observers of a Linked or Derived property are registered on the property it refers to;
+// they are kept here as well, so that they can be detached from it and moved elsewhere.
+type SharedObserver<T> = Rc<RefCell<Box<FnMut(&T)>>>;
+
+fn detach<T>(observers: &mut Vec<(usize, Subscription, SharedObserver<T>)>,
+             unobserve: &Fn(Subscription)) -> Vec<(usize, Box<FnMut(&T)>)>
+        where T: 'static {
+    observers.drain(..).map(|(id, subscription, observer)| {
+        unobserve(subscription);
+        (id, Box::new(move |value: &T| (&mut *observer.borrow_mut())(value)) as Box<FnMut(&T)>)
+    }).collect()
+}
+
 struct Linked<T> {
-    property:  Rc<Property<T>>
+    property:  Rc<Property<T>>,
+    observers: Vec<(usize, Subscription, SharedObserver<T>)>
 }
 
 impl<T> Linked<T> where T: 'static {
     fn new(other: Rc<Property<T>>) -> Box<Observable<T>> {
         Box::new(Linked {
-            property: other
+            property:  other,
+            observers: Vec::new()
         })
     }
 }
 
-impl<T> Observable<T> for Linked<T> {
+impl<T> Observable<T> for Linked<T> where T: 'static {
     fn read(&self, reader: &mut FnMut(&T)) {
         self.property.0.borrow().read(reader)
     }
 
-    fn write(&mut self, writer: &mut FnMut(&mut T)) {
-        self.property.0.borrow_mut().write(writer)
+    fn change(&mut self, writer: &mut FnMut(&mut T)) {
+        self.property.0.borrow_mut().change(writer)
+    }
+
+    fn announce(&mut self) {
+        self.property.0.borrow_mut().announce()
+    }
+
+    fn key(&self) -> usize {
+        self.property.0.borrow().key()
+    }
+
+    fn observe(&mut self, id: usize, observer: Box<FnMut(&T) + 'static>) {
+        let observer = Rc::new(RefCell::new(observer));
+        let forward = observer.clone();
+        let subscription = self.property.subscribe(Box::new(move |value|
+            (&mut *forward.borrow_mut())(value)));
+        self.observers.push((id, subscription, observer))
+    }
+
+    fn unobserve(&mut self, id: usize) {
+        let property = &self.property;
+        self.observers.retain(|&(observer_id, subscription, _)| {
+            if observer_id == id { property.unobserve(subscription) }
+            observer_id != id
+        })
     }
 
-    fn observe(&mut self, observer: Box<FnMut(&T) + 'static>) {
-        self.property.0.borrow_mut().observe(observer)
+    fn destruct(&mut self) -> Vec<(usize, Box<FnMut(&T)>)> {
+        let property = self.property.clone();
+        detach(&mut self.observers, &move |subscription| property.unobserve(subscription))
     }
 
-    fn destruct(&mut self) -> Vec<Box<FnMut(&T)>> {
-        Vec::new()
+    fn busy(&self) -> bool {
+        self.property.busy()
     }
 }
 
 struct Derived<T, U> {
-    property: Rc<Property<U>>,
-    map_to:   Box<Fn(&U, T) -> U + 'static>,
-    map_from: Rc<Box<Fn(&U) -> T + 'static>>
+    property:  Rc<Property<U>>,
+    map_to:    Box<Fn(&U, T) -> U + 'static>,
+    map_from:  Rc<Box<Fn(&U) -> T + 'static>>,
+    observers: Vec<(usize, Subscription, SharedObserver<T>)>
 }
 
 impl<T, U> Derived<T, U> where T: 'static, U: 'static {
     fn new<MT, MF>(other: Rc<Property<U>>, map_to: MT, map_from: MF) -> Box<Observable<T>>
             where MT: Fn(&U, T) -> U + 'static, MF: Fn(&U) -> T + 'static {
         Box::new(Derived {
-            property: other.clone(),
-            map_to:   Box::new(map_to),
-            map_from: Rc::new(Box::new(map_from))
+            property:  other.clone(),
+            map_to:    Box::new(map_to),
+            map_from:  Rc::new(Box::new(map_from)),
+            observers: Vec::new()
         })
     }
 }
@@ -108,69 +170,107 @@ impl<T, U> Observable<T> for Derived<T, U> where T: 'static, U: 'static {
             reader(&(*self.map_from)(linked_value)))
     }
 
-    fn write(&mut self, writer: &mut FnMut(&mut T)) {
+    fn change(&mut self, writer: &mut FnMut(&mut T)) {
         let mut observable = self.property.0.borrow_mut();
-        observable.write(&mut |linked_value| {
+        observable.change(&mut |linked_value| {
             let mut value = (*self.map_from)(linked_value);
             writer(&mut value);
             *linked_value = (*self.map_to)(&linked_value, value)
         })
     }
 
-    fn observe(&mut self, mut observer: Box<FnMut(&T) + 'static>) {
+    fn announce(&mut self) {
+        self.property.0.borrow_mut().announce()
+    }
+
+    fn key(&self) -> usize {
+        self.property.0.borrow().key()
+    }
+
+    fn observe(&mut self, id: usize, observer: Box<FnMut(&T) + 'static>) {
+        let observer = Rc::new(RefCell::new(observer));
+        let forward = observer.clone();
         let map_from = self.map_from.clone();
-        let mut observable = self.property.0.borrow_mut();
-        observable.observe(Box::new(move |linked_value|
-            observer(&(*map_from)(linked_value))))
+        let subscription = self.property.subscribe(Box::new(move |linked_value|
+            (&mut *forward.borrow_mut())(&(*map_from)(linked_value))));
+        self.observers.push((id, subscription, observer))
+    }
+
+    fn unobserve(&mut self, id: usize) {
+        let property = &self.property;
+        self.observers.retain(|&(observer_id, subscription, _)| {
+            if observer_id == id { property.unobserve(subscription) }
+            observer_id != id
+        })
+    }
+
+    fn destruct(&mut self) -> Vec<(usize, Box<FnMut(&T)>)> {
+        let property = self.property.clone();
+        detach(&mut self.observers, &move |subscription| property.unobserve(subscription))
     }
 
-    fn destruct(&mut self) -> Vec<Box<FnMut(&T)>> {
-        Vec::new()
+    fn busy(&self) -> bool {
+        self.property.busy()
     }
 }
 
-pub struct Property<T>(RefCell<Box<Observable<T>>>);
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Subscription(usize);
+
+/// A value that can be observed, and linked to or derived from other properties.
+///
+/// Properties and their observers are confined to the thread that created them. To reach
+/// another thread, such as the camera thread, an observer sends a message through
+/// a channel (see `notify`), and the other thread replies in the same way; or the value
+/// can be kept in a `SyncProperty` instead.
+pub struct Property<T>(RefCell<Box<Observable<T>>>, Cell<usize>);
 
 impl<T> Property<T> where T: 'static {
+    fn from_observable(observable: Box<Observable<T>>) -> Property<T> {
+        Property(RefCell::new(observable), Cell::new(0))
+    }
+
     pub fn new(initial: T) -> Rc<Property<T>> {
-        Rc::new(Property(RefCell::new(Root::new(initial, |_| ()))))
+        Rc::new(Property::from_observable(Root::new(initial, |_| ())))
     }
 
     pub fn with_validator<V>(mut initial: T, mut validator: V) -> Rc<Property<T>>
             where V: FnMut(&mut T) + 'static {
         validator(&mut initial);
-        Rc::new(Property(RefCell::new(Root::new(initial, validator))))
+        Rc::new(Property::from_observable(Root::new(initial, validator)))
     }
 
     pub fn linked(other: Rc<Property<T>>) -> Rc<Property<T>> {
-        Rc::new(Property(RefCell::new(Linked::new(other))))
+        Rc::new(Property::from_observable(Linked::new(other)))
     }
 
     pub fn derived<MT, MF, U>(other: Rc<Property<U>>, map_to: MT, map_from: MF) -> Rc<Property<T>>
             where MT: Fn(&U, T) -> U + 'static, MF: Fn(&U) -> T + 'static, U: 'static {
-        Rc::new(Property(RefCell::new(Derived::new(other, map_to, map_from))))
+        Rc::new(Property::from_observable(Derived::new(other, map_to, map_from)))
     }
 
-    pub fn link(&self, other: Rc<Property<T>>) {
-        let mut replaced = Linked::new(other);
+    fn replace(&self, mut replaced: Box<Observable<T>>) {
         mem::swap(&mut *self.0.borrow_mut(), &mut replaced);
 
+        // Observers keep their ids, so that outstanding subscriptions remain valid.
         let mut observers = replaced.destruct();
-        for observer in observers.drain(..) {
-            self.0.borrow_mut().observe(observer)
+        for (id, observer) in observers.drain(..) {
+            self.0.borrow_mut().observe(id, observer)
         }
     }
 
+    pub fn link(&self, other: Rc<Property<T>>) {
+        self.replace(Linked::new(other))
+    }
+
     pub fn derive<MT, MF, U>(&self, other: Rc<Property<U>>,
                              map_to: MT, map_from: MF)
             where MT: Fn(&U, T) -> U + 'static, MF: Fn(&U) -> T + 'static, U: 'static {
-        let mut replaced = Derived::new(other, map_to, map_from);
-        mem::swap(&mut *self.0.borrow_mut(), &mut replaced);
+        self.replace(Derived::new(other, map_to, map_from))
+    }
 
-        let mut observers = replaced.destruct();
-        for observer in observers.drain(..) {
-            self.0.borrow_mut().observe(observer)
-        }
+    pub fn unlink(&self) where T: Clone {
+        self.replace(Root::new(self.get(), |_| ()))
     }
 
     pub fn read<F, R>(&self, mut reader: F) -> R where F: FnMut(&T) -> R {
@@ -183,44 +283,158 @@ impl<T> Property<T> where T: 'static {
     pub fn write<F, R>(&self, mut writer: F) -> R where F: FnMut(&mut T) -> R {
         let mut observable = self.0.borrow_mut();
         let mut result = None;
-        observable.write(&mut |value| result = Some(writer(value)));
+        observable.change(&mut |value| result = Some(writer(value)));
+        observable.announce();
         result.unwrap()
     }
 
-    pub fn observe<F>(&self, observer: F)
+    fn subscribe(&self, observer: Box<FnMut(&T) + 'static>) -> Subscription {
+        let id = self.1.get();
+        self.1.set(id + 1);
+
+        let mut observable = self.0.borrow_mut();
+        observable.observe(id, observer);
+        Subscription(id)
+    }
+
+    pub fn observe<F>(&self, observer: F) -> Subscription
             where F: Fn(&T) + 'static {
+        self.subscribe(Box::new(observer))
+    }
+
+    pub fn unobserve(&self, subscription: Subscription) {
+        let Subscription(id) = subscription;
         let mut observable = self.0.borrow_mut();
-        observable.observe(Box::new(observer))
+        observable.unobserve(id)
     }
 
     pub fn get(&self) -> T where T: Clone {
         self.read(|value| value.clone())
     }
 
+    // Whether this property, or one it is linked to or derived from, is being read or
+    // changed, so that it can't be changed right now.
+    fn busy(&self) -> bool {
+        match self.0.borrow_state() {
+            BorrowState::Unused => self.0.borrow().busy(),
+            _ => true
+        }
+    }
+
+    /// Changes the value. If this is called from an observer while the property is being
+    /// changed already, e.g. because of a feedback loop between linked properties, the new
+    /// value is ignored.
     pub fn set(&self, new_value: T) where T: Clone {
+        if self.busy() {
+            let _ = writeln!(io::stderr(),
+                             "Property changed from within its own observer; change ignored");
+            return
+        }
         self.write(move |value| *value = new_value.clone())
     }
 
-    pub fn notify<M, R>(&self, channel: &Sender<R>, map: M)
+    pub fn notify<M, R>(&self, channel: &Sender<R>, map: M) -> Subscription
             where M: Fn(&T) -> R + 'static, R: 'static {
         let channel = channel.clone();
         self.observe(move |value| { channel.send(map(value)).unwrap_or(()) })
     }
 
-    pub fn propagate<M, R>(&self, other: Rc<Property<R>>, map: M)
+    pub fn propagate<M, R>(&self, other: Rc<Property<R>>, map: M) -> Subscription
             where M: Fn(&T) -> R + 'static, R: 'static {
-        self.observe(move |value| { other.write(|other_value| *other_value = map(value)) })
+        self.observe(move |value| {
+            // A property propagated back to this one would otherwise panic.
+            if other.busy() { return }
+            other.write(|other_value| *other_value = map(value))
+        })
+    }
+
+    /// Returns a property that always holds `map` applied to the value of this one.
+    /// It is meant to be read only; anything written to it is lost on the next change.
+    pub fn map<M, R>(&self, map: M) -> Rc<Property<R>>
+            where M: Fn(&T) -> R + 'static, R: 'static {
+        let mapped = Property::new(self.read(&map));
+        self.propagate(mapped.clone(), map);
+        mapped
+    }
+
+    /// Returns a property that always holds `zip` applied to the values of this property
+    /// and `other`. Like with `map`, it is meant to be read only.
+    pub fn zip<U, M, R>(&self, other: Rc<Property<U>>, zip: M) -> Rc<Property<R>>
+            where M: Fn(&T, &U) -> R + 'static, T: Clone, U: Clone + 'static, R: 'static {
+        // Both values are kept here, so that an observer of one property does not have
+        // to read the other one, which could be linked to the first and already borrowed.
+        // This also makes the order in which the observers first fire irrelevant.
+        let values = Rc::new(RefCell::new((self.get(), other.get())));
+        let zip = Rc::new(zip);
+        let zipped = {
+            let values = values.borrow();
+            Property::new(zip(&values.0, &values.1))
+        };
+
+        {
+            let (values, zip, zipped) = (values.clone(), zip.clone(), zipped.clone());
+            self.observe(move |value| {
+                values.borrow_mut().0 = value.clone();
+                let values = values.borrow();
+                zipped.write(|zipped_value| *zipped_value = zip(&values.0, &values.1))
+            });
+        }
+        {
+            let (values, zip, zipped) = (values.clone(), zip.clone(), zipped.clone());
+            other.observe(move |value| {
+                values.borrow_mut().1 = value.clone();
+                let values = values.borrow();
+                zipped.write(|zipped_value| *zipped_value = zip(&values.0, &values.1))
+            });
+        }
+        zipped
+    }
+}
+
+/// Changes made to properties within `batch`.
+pub struct Batch {
+    announcements: Vec<(usize, Box<Fn()>)>
+}
+
+impl Batch {
+    /// Changes the value of `property` right away, but lets its observers know only
+    /// once the whole batch is done, and only once per property.
+    pub fn set<T>(&mut self, property: &Rc<Property<T>>, new_value: T)
+            where T: Clone + 'static {
+        if property.busy() {
+            let _ = writeln!(io::stderr(),
+                             "Property changed from within its own observer; change ignored");
+            return
+        }
+
+        let key = {
+            let mut observable = property.0.borrow_mut();
+            observable.change(&mut |value| *value = new_value.clone());
+            observable.key()
+        };
+        // Linked properties share a key, so their observers still fire only once.
+        if self.announcements.iter().all(|&(other_key, _)| other_key != key) {
+            let property = property.clone();
+            self.announcements.push((key, Box::new(move ||
+                property.0.borrow_mut().announce())))
+        }
+    }
+}
+
+/// Changes several related properties together, e.g. color temperature and tint, so that
+/// their observers never see only some of the changes, and fire once each rather than
+/// once per change.
+pub fn batch<F>(changes: F) where F: FnOnce(&mut Batch) {
+    let mut batch = Batch { announcements: Vec::new() };
+    changes(&mut batch);
+    for (_, announce) in batch.announcements {
+        announce()
     }
 }
 
 impl<T> Default for Property<T> where T: Default + 'static {
     fn default() -> Property<T> {
-        let value = Default::default();
-        Property(RefCell::new(Box::new(Root {
-            value:     value,
-            validator: Box::new(|_| ()),
-            observers: Vec::new()
-        })))
+        Property::from_observable(Root::new(Default::default(), |_| ()))
     }
 }
 
@@ -238,10 +452,189 @@ impl<T> serde::Deserialize for Property<T> where T: serde::Deserialize + 'static
         where D: serde::Deserializer,
     {
         let value = try!(serde::Deserialize::deserialize(deserializer));
-        Ok(Property(RefCell::new(Box::new(Root {
-            value:     value,
-            validator: Box::new(|_| ()),
+        Ok(Property::from_observable(Root::new(value, |_| ())))
+    }
+}
+
+/// A property that can be shared between threads.
+///
+/// Observers run on the thread that changes the value, after the lock protecting it
+/// has been released, so they may freely read or change the property themselves.
+/// Unlike `Property`, it can't be linked to or derived from other properties.
+pub struct SyncProperty<T>(Mutex<SyncState<T>>);
+
+struct SyncState<T> {
+    value:     T,
+    next_id:   usize,
+    observers: Vec<(usize, Arc<Fn(&T) + Send + Sync>)>
+}
+
+impl<T> SyncProperty<T> where T: Clone + Send + 'static {
+    pub fn new(initial: T) -> Arc<SyncProperty<T>> {
+        Arc::new(SyncProperty(Mutex::new(SyncState {
+            value:     initial,
+            next_id:   0,
             observers: Vec::new()
-        }))))
+        })))
+    }
+
+    pub fn get(&self) -> T {
+        self.0.lock().unwrap().value.clone()
+    }
+
+    pub fn set(&self, new_value: T) {
+        let observers: Vec<_> = {
+            let mut state = self.0.lock().unwrap();
+            state.value = new_value.clone();
+            state.observers.iter().map(|&(_, ref observer)| observer.clone()).collect()
+        };
+        for observer in observers {
+            observer(&new_value)
+        }
+    }
+
+    pub fn observe<F>(&self, observer: F) -> Subscription
+            where F: Fn(&T) + Send + Sync + 'static {
+        let observer: Arc<Fn(&T) + Send + Sync> = Arc::new(observer);
+        let (id, value) = {
+            let mut state = self.0.lock().unwrap();
+            let id = state.next_id;
+            state.next_id += 1;
+            state.observers.push((id, observer.clone()));
+            (id, state.value.clone())
+        };
+
+        // Like with `Property`, the observer gets the current value right away.
+        observer(&value);
+        Subscription(id)
+    }
+
+    pub fn unobserve(&self, subscription: Subscription) {
+        let Subscription(id) = subscription;
+        self.0.lock().unwrap().observers.retain(|&(observer_id, _)| observer_id != id)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::Cell;
+    use std::rc::Rc;
+    use super::*;
+
+    #[test]
+    fn unlink_detaches_from_linked_property() {
+        let camera = Property::new(1);
+        let slider = Property::linked(camera.clone());
+        slider.set(2);
+        assert_eq!(camera.get(), 2);
+
+        slider.unlink();
+        assert_eq!(slider.get(), 2);
+        slider.set(3);
+        assert_eq!(camera.get(), 2);
+        camera.set(4);
+        assert_eq!(slider.get(), 3);
+    }
+
+    #[test]
+    fn unlink_keeps_observers() {
+        let camera = Property::new(1);
+        let slider = Property::linked(camera.clone());
+        let seen = Rc::new(Cell::new(0));
+        {
+            let seen = seen.clone();
+            slider.observe(move |&value| seen.set(value));
+        }
+
+        slider.unlink();
+        slider.set(5);
+        assert_eq!(seen.get(), 5);
+        camera.set(6);
+        assert_eq!(seen.get(), 5);
+    }
+
+    #[test]
+    fn unobserved_observer_is_not_called() {
+        let property = Property::new(0);
+        let calls = Rc::new(Cell::new(0));
+        let subscription = {
+            let calls = calls.clone();
+            property.observe(move |_| calls.set(calls.get() + 1))
+        };
+        // Observers are called once right away.
+        assert_eq!(calls.get(), 1);
+
+        property.set(1);
+        assert_eq!(calls.get(), 2);
+        property.unobserve(subscription);
+        property.set(2);
+        assert_eq!(calls.get(), 2);
+    }
+
+    #[test]
+    fn unobserve_works_on_linked_property() {
+        let camera = Property::new(0);
+        let slider = Property::linked(camera.clone());
+        let calls = Rc::new(Cell::new(0));
+        let subscription = {
+            let calls = calls.clone();
+            slider.observe(move |_| calls.set(calls.get() + 1))
+        };
+
+        slider.unobserve(subscription);
+        camera.set(1);
+        slider.set(2);
+        assert_eq!(calls.get(), 1);
+    }
+
+    #[test]
+    fn map_follows_source() {
+        let exposure_us = Property::new(2000);
+        let label = exposure_us.map(|value| format!("{} ms", value / 1000));
+        assert_eq!(label.get(), "2 ms");
+        exposure_us.set(5000);
+        assert_eq!(label.get(), "5 ms");
+    }
+
+    #[test]
+    fn zip_follows_both_sources() {
+        let temperature = Property::new(6500);
+        let tint = Property::new(1000);
+        let label = temperature.zip(tint.clone(), |temperature, tint|
+            format!("{}/{}", temperature, tint));
+        assert_eq!(label.get(), "6500/1000");
+        temperature.set(5500);
+        assert_eq!(label.get(), "5500/1000");
+        tint.set(900);
+        assert_eq!(label.get(), "5500/900");
+    }
+
+    #[test]
+    fn zip_of_linked_properties() {
+        let camera = Property::new(1);
+        let slider = Property::linked(camera.clone());
+        let sum = camera.zip(slider.clone(), |a, b| a + b);
+        assert_eq!(sum.get(), 2);
+        slider.set(3);
+        assert_eq!(sum.get(), 6);
+    }
+
+    #[test]
+    fn feedback_loop_does_not_panic() {
+        let camera = Property::new(1);
+        let slider = Property::new(1);
+        {
+            let slider = slider.clone();
+            camera.observe(move |&value| slider.set(value));
+        }
+        {
+            let camera = camera.clone();
+            slider.observe(move |&value| camera.set(value));
+        }
+
+        camera.set(2);
+        assert_eq!((camera.get(), slider.get()), (2, 2));
+        slider.set(3);
+        assert_eq!((camera.get(), slider.get()), (3, 3));
     }
 }
@@ -0,0 +1,108 @@
+extern crate touptek;
+
+use std::cell::{Cell, RefCell};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{sync_channel, SyncSender, TrySendError};
+use std::thread::{self, JoinHandle};
+
+use camera;
+
+// How many frames may wait to be written before new ones are dropped.
+const QUEUE_LENGTH: usize = 16;
+
+pub struct Recorder {
+    frame_tx: RefCell<Option<SyncSender<touptek::Image>>>,
+    writer: RefCell<Option<JoinHandle<Result<(), String>>>>,
+    recorded: Cell<u64>,
+    dropped: Cell<u64>,
+}
+
+impl Recorder {
+    pub fn new() -> Recorder {
+        Recorder {
+            frame_tx: RefCell::new(None),
+            writer: RefCell::new(None),
+            recorded: Cell::new(0),
+            dropped: Cell::new(0),
+        }
+    }
+
+    /// Starts writing frames as numbered PNG files into a new subdirectory of `directory`.
+    pub fn start(&self, directory: &Path) -> Result<PathBuf, String> {
+        self.stop();
+
+        let directory = next_recording_path(directory);
+        try!(fs::create_dir_all(&directory).map_err(|err|
+            format!("cannot create {}: {}", directory.display(), err)));
+
+        let (frame_tx, frame_rx) = sync_channel(QUEUE_LENGTH);
+        let writer = {
+            let directory = directory.clone();
+            thread::spawn(move || {
+                let mut index = 1;
+                for image in frame_rx.iter() {
+                    let path = directory.join(format!("frame-{:06}.png", index));
+                    try!(camera::store_png(image, &path).map_err(|err|
+                        format!("cannot write {}: {}", path.display(), err)));
+                    index += 1
+                }
+                Ok(())
+            })
+        };
+
+        *self.frame_tx.borrow_mut() = Some(frame_tx);
+        *self.writer.borrow_mut() = Some(writer);
+        self.recorded.set(0);
+        self.dropped.set(0);
+        Ok(directory)
+    }
+
+    /// Stops recording; frames that are already queued are still written.
+    pub fn stop(&self) {
+        self.frame_tx.borrow_mut().take();
+        self.writer.borrow_mut().take();
+    }
+
+    pub fn is_recording(&self) -> bool {
+        self.frame_tx.borrow().is_some()
+    }
+
+    pub fn recorded_frames(&self) -> u64 { self.recorded.get() }
+    pub fn dropped_frames(&self) -> u64 { self.dropped.get() }
+
+    /// Queues a frame for writing. Once the writer thread fails, recording stops
+    /// and its error is returned.
+    pub fn record(&self, image: &touptek::Image) -> Result<(), String> {
+        let result = match *self.frame_tx.borrow() {
+            Some(ref frame_tx) => frame_tx.try_send(image.clone()),
+            None => return Ok(())
+        };
+
+        match result {
+            Ok(()) => self.recorded.set(self.recorded.get() + 1),
+            // The disk can't keep up; better lose a frame than run out of memory.
+            Err(TrySendError::Full(_)) => self.dropped.set(self.dropped.get() + 1),
+            // The writer thread has failed and already exited, so joining doesn't block.
+            Err(TrySendError::Disconnected(_)) => {
+                let writer = self.writer.borrow_mut().take();
+                self.stop();
+                return match writer.map(|writer| writer.join()) {
+                    Some(Ok(Err(err))) => Err(err),
+                    Some(Err(_)) => Err("writer thread panicked".to_string()),
+                    _ => Err("writer thread exited".to_string()),
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+fn next_recording_path(directory: &Path) -> PathBuf {
+    let mut index = 1;
+    loop {
+        let path = directory.join(format!("recording-{:04}", index));
+        if fs::metadata(&path).is_err() { return path }
+        index += 1
+    }
+}
@@ -1,4 +1,4 @@
-use std::ops::{Add, Mul, Sub};
+use std::ops::{Add, Div, Mul, Sub};
 
 #[derive(Clone, Copy, PartialEq, Debug)]
 pub struct Point(pub f32, pub f32);
@@ -21,7 +21,25 @@ impl Mul<f32> for Point {
     fn mul(self, rhs: f32) -> Point { Point(self.0 * rhs, self.1 * rhs) }
 }
 
+impl Div<f32> for Point {
+    type Output = Point;
+    fn div(self, rhs: f32) -> Point { Point(self.0 / rhs, self.1 / rhs) }
+}
+
 impl Point {
+    pub fn length(self) -> f32 {
+        (self.0 * self.0 + self.1 * self.1).sqrt()
+    }
+
+    pub fn distance(self, other: Point) -> f32 {
+        (other - self).length()
+    }
+
+    /// Interpolates linearly between `self` at `t` = 0 and `other` at `t` = 1.
+    pub fn lerp(self, other: Point, t: f32) -> Point {
+        self + (other - self) * t
+    }
+
     pub fn round(self) -> Point {
         Point(self.0.round(), self.1.round())
     }
@@ -32,11 +50,46 @@ impl Point {
 }
 
 impl Rect {
+    pub fn from_corners(Point(x1, y1): Point, Point(x2, y2): Point) -> Rect {
+        let (left, top) = (x1.min(x2), y1.min(y2));
+        Rect(Point(left, top), Point(x1.max(x2) - left, y1.max(y2) - top))
+    }
+
+    pub fn origin(self) -> Point { self.0 }
+    pub fn size(self) -> Point { self.1 }
+
+    pub fn center(self) -> Point {
+        let Rect(origin, size) = self;
+        origin + size / 2.
+    }
+
     pub fn contains(self, point: Point) -> bool {
         let Rect(Point(l, t), Point(w, h)) = self;
         let Point(x, y) = point;
         x >= l && y >= t && x <= l + w && y <= t + h
     }
+
+    /// Returns the area covered by both rectangles, or `None` if they do not overlap;
+    /// rectangles that only share an edge do not overlap.
+    pub fn intersect(self, other: Rect) -> Option<Rect> {
+        let (Rect(Point(l1, t1), Point(w1, h1)), Rect(Point(l2, t2), Point(w2, h2))) =
+            (self, other);
+        let (left, top) = (l1.max(l2), t1.max(t2));
+        let (right, bottom) = ((l1 + w1).min(l2 + w2), (t1 + h1).min(t2 + h2));
+        if right > left && bottom > top {
+            Some(Rect(Point(left, top), Point(right - left, bottom - top)))
+        } else {
+            None
+        }
+    }
+
+    /// Returns the smallest rectangle covering both rectangles.
+    pub fn union(self, other: Rect) -> Rect {
+        let (Rect(Point(l1, t1), Point(w1, h1)), Rect(Point(l2, t2), Point(w2, h2))) =
+            (self, other);
+        Rect::from_corners(Point(l1.min(l2), t1.min(t2)),
+                           Point((l1 + w1).max(l2 + w2), (t1 + h1).max(t2 + h2)))
+    }
 }
 
 #[derive(Clone, Copy, PartialEq, Eq, Debug)]
@@ -44,3 +97,66 @@ pub enum Direction {
     Horizontal,
     Vertical
 }
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Alignment {
+    Start,
+    Center,
+    End,
+    Stretch
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Point, Rect};
+
+    #[test]
+    fn rects_touching_at_an_edge_do_not_intersect() {
+        let left = Rect(Point(0., 0.), Point(10., 10.));
+        let right = Rect(Point(10., 0.), Point(10., 10.));
+        assert_eq!(left.intersect(right), None);
+        assert_eq!(left.union(right), Rect(Point(0., 0.), Point(20., 10.)));
+    }
+
+    #[test]
+    fn rect_inside_another_intersects_as_itself() {
+        let outer = Rect(Point(0., 0.), Point(10., 10.));
+        let inner = Rect(Point(2., 3.), Point(4., 5.));
+        assert_eq!(outer.intersect(inner), Some(inner));
+        assert_eq!(inner.intersect(outer), Some(inner));
+        assert_eq!(outer.union(inner), outer);
+        assert_eq!(inner.union(outer), outer);
+    }
+
+    #[test]
+    fn overlapping_rects() {
+        let a = Rect(Point(0., 0.), Point(10., 10.));
+        let b = Rect(Point(5., -5.), Point(10., 10.));
+        assert_eq!(a.intersect(b), Some(Rect(Point(5., 0.), Point(5., 5.))));
+        assert_eq!(a.union(b), Rect(Point(0., -5.), Point(15., 15.)));
+        assert_eq!(b.center(), Point(10., 0.));
+    }
+
+    #[test]
+    fn point_arithmetic() {
+        assert_eq!(Point(1., 2.) + Point(3., 5.), Point(4., 7.));
+        assert_eq!(Point(1., 2.) - Point(3., 5.), Point(-2., -3.));
+        assert_eq!(Point(1., 2.) * 2., Point(2., 4.));
+        assert_eq!(Point(1., 2.) / 2., Point(0.5, 1.));
+    }
+
+    #[test]
+    fn point_length_and_distance() {
+        assert_eq!(Point(3., 4.).length(), 5.);
+        assert_eq!(Point(1., 1.).distance(Point(4., 5.)), 5.);
+        assert_eq!(Point(4., 5.).distance(Point(1., 1.)), 5.);
+    }
+
+    #[test]
+    fn point_lerp() {
+        let (start, end) = (Point(0., 10.), Point(10., 20.));
+        assert_eq!(start.lerp(end, 0.), start);
+        assert_eq!(start.lerp(end, 0.5), Point(5., 15.));
+        assert_eq!(start.lerp(end, 1.), end);
+    }
+}
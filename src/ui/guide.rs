@@ -0,0 +1,127 @@
+extern crate nanovg;
+
+use std::rc::Rc;
+
+use property::Property;
+use ui::{Point, Rect};
+
+/// A mark drawn over the background to help with alignment or composition. Guides are
+/// not widgets; they don't take any input, and are hidden with the background.
+pub trait Guide {
+    /// Draws the guide over `image`, the area on screen covered by the background,
+    /// which is `pixels` in size, with rotation applied.
+    fn draw(&self, nvg: &nanovg::Context, image: Rect, pixels: Point);
+}
+
+const GUIDE_LINE_WIDTH: f32 = 2.; // pixels
+
+// Crosshair
+
+/// Lines through the center of the image, and optionally a circle around it.
+pub struct Crosshair {
+    color: nanovg::Color,
+    circle: bool,
+    shown: Rc<Property<bool>>,
+}
+
+impl Crosshair {
+    pub fn new(color: nanovg::Color) -> Crosshair {
+        Crosshair {
+            color: color,
+            circle: false,
+            shown: Property::new(true),
+        }
+    }
+
+    pub fn with_circle(self) -> Crosshair {
+        Crosshair { circle: true, ..self }
+    }
+
+    pub fn shown(&self) -> Rc<Property<bool>> { self.shown.clone() }
+}
+
+impl Guide for Crosshair {
+    fn draw(&self, nvg: &nanovg::Context, image: Rect, _pixels: Point) {
+        if !self.shown.get() { return }
+
+        let Rect(Point(left, top), Point(width, height)) = image;
+        let Point(center_x, center_y) = image.center();
+        nvg.begin_path();
+        nvg.move_to(left, center_y);
+        nvg.line_to(left + width, center_y);
+        nvg.move_to(center_x, top);
+        nvg.line_to(center_x, top + height);
+        if self.circle {
+            nvg.circle(center_x, center_y, width.min(height) / 8.);
+        }
+        nvg.stroke_width(GUIDE_LINE_WIDTH);
+        nvg.stroke_color(self.color);
+        nvg.stroke();
+    }
+}
+
+// Grid
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum GridSpacing {
+    /// Divides the image into this many equal parts each way; 3 is the rule of thirds.
+    Divisions(u32),
+    /// Puts a line every so many image pixels.
+    Pixels(f32),
+}
+
+// Lines closer than this on screen are not drawn, as they would only cover the image.
+const GRID_MIN_SPACING: f32 = 4.; // pixels
+
+pub struct Grid {
+    color: nanovg::Color,
+    spacing: Rc<Property<GridSpacing>>,
+    shown: Rc<Property<bool>>,
+}
+
+impl Grid {
+    pub fn new(color: nanovg::Color, spacing: GridSpacing) -> Grid {
+        Grid {
+            color: color,
+            spacing: Property::new(spacing),
+            shown: Property::new(true),
+        }
+    }
+
+    pub fn spacing(&self) -> Rc<Property<GridSpacing>> { self.spacing.clone() }
+    pub fn shown(&self) -> Rc<Property<bool>> { self.shown.clone() }
+}
+
+impl Guide for Grid {
+    fn draw(&self, nvg: &nanovg::Context, image: Rect, pixels: Point) {
+        if !self.shown.get() { return }
+
+        let Rect(Point(left, top), Point(width, height)) = image;
+        let (step_x, step_y) = match self.spacing.get() {
+            GridSpacing::Divisions(0) => return,
+            GridSpacing::Divisions(count) =>
+                (width / count as f32, height / count as f32),
+            GridSpacing::Pixels(pitch) =>
+                (pitch * width / pixels.0, pitch * height / pixels.1),
+        };
+        if step_x < GRID_MIN_SPACING || step_y < GRID_MIN_SPACING { return }
+
+        nvg.begin_path();
+        // Stopping short of the far edge avoids drawing a line over it due to rounding.
+        let mut x = left + step_x;
+        while x < left + width - 0.5 {
+            nvg.move_to(x, top);
+            nvg.line_to(x, top + height);
+            x += step_x
+        }
+        let mut y = top + step_y;
+        while y < top + height - 0.5 {
+            nvg.move_to(left, y);
+            nvg.line_to(left + width, y);
+            y += step_y
+        }
+        nvg.stroke_width(GUIDE_LINE_WIDTH / 2.);
+        nvg.stroke_color(self.color);
+        nvg.stroke();
+    }
+}
@@ -4,18 +4,28 @@ extern crate nanovg;
 extern crate touptek;
 extern crate png;
 
-use std::cell::RefCell;
+use std::cell::{Cell, RefCell};
+use std::f32;
+use std::path::Path;
 
 use ui::{Point, Rect};
 
 pub struct Image<'a> {
     nvg: &'a nanovg::Context,
     nvg_image: RefCell<Option<nanovg::Image>>,
+    orientation: Cell<(bool, bool, u8)>,
+    // A copy of the RGBA pixels of a camera image, with its width, for `pixel`.
+    pixels: RefCell<Option<(u32, Vec<u8>)>>,
 }
 
 impl<'a> Image<'a> {
     pub fn new(nvg: &nanovg::Context) -> Image {
-        Image { nvg: nvg, nvg_image: RefCell::new(None) }
+        Image {
+            nvg: nvg,
+            nvg_image: RefCell::new(None),
+            orientation: Cell::new((false, false, 0)),
+            pixels: RefCell::new(None),
+        }
     }
 
     pub fn present(&self) -> bool {
@@ -29,63 +39,173 @@ impl<'a> Image<'a> {
 
     pub fn unset(&self) {
         self.nvg.delete_image(self.nvg_image.borrow_mut().take().unwrap());
+        *self.pixels.borrow_mut() = None
+    }
+
+    /// Mirrors the image and then rotates it clockwise by `rotate_quadrants` * 90°.
+    pub fn set_orientation(&self, flip_h: bool, flip_v: bool, rotate_quadrants: u8) {
+        self.orientation.set((flip_h, flip_v, rotate_quadrants % 4))
+    }
+
+    pub fn size(&self) -> Point {
+        let nvg_image = self.nvg_image.borrow();
+        let nvg_image = nvg_image.as_ref().unwrap();
+
+        let (width, height) = self.nvg.image_size(nvg_image);
+        Point(width as f32, height as f32)
+    }
+
+    /// Size of the image as it appears on screen, i.e. with rotation applied.
+    pub fn oriented_size(&self) -> Point {
+        let Point(width, height) = self.size();
+        let (_, _, quadrants) = self.orientation.get();
+        if quadrants % 2 == 1 { Point(height, width) } else { Point(width, height) }
     }
 
-    pub fn from_touptek(&self, mut raw_image: touptek::Image) {
+    pub fn from_touptek(&self, raw_image: touptek::Image) {
         let touptek::Resolution { width, height } = raw_image.resolution;
         self.set(self.nvg.create_image_rgba(width, height, &raw_image.data).unwrap());
-        unsafe { raw_image.data.set_len(0) } // O(1) drop at -O1
+        // nanovg copies the pixels, but they are kept, without copying them again,
+        // so that `pixel` can look them up.
+        *self.pixels.borrow_mut() = Some((width, raw_image.data))
+    }
+
+    /// Returns the RGBA value of the pixel at `point`, in image coordinates, if the image
+    /// came from the camera and `point` is within it.
+    pub fn pixel(&self, point: Point) -> Option<[u8; 4]> {
+        let pixels = self.pixels.borrow();
+        let (width, data) = match *pixels {
+            Some((width, ref data)) => (width, data),
+            None => return None
+        };
+        let Point(x, y) = point;
+        if x < 0. || y < 0. || x >= width as f32 { return None }
+
+        let offset = (y as usize * width as usize + x as usize) * 4;
+        if offset + 4 > data.len() { return None }
+        Some([data[offset], data[offset + 1], data[offset + 2], data[offset + 3]])
     }
 
-    pub fn from_png(&self, raw_image: png::Image) {
+    pub fn from_png(&self, raw_image: png::Image) -> Result<(), String> {
         match raw_image.pixels {
-            png::PixelsByColorType::RGBA8(ref data) =>
+            png::PixelsByColorType::RGBA8(ref data) => {
                 self.set(self.nvg.create_image_rgba(
-                            raw_image.width, raw_image.height, data).unwrap()),
-            _ => panic!("unsupported non-RGBA png format")
+                            raw_image.width, raw_image.height, data).unwrap());
+                Ok(())
+            }
+            _ => Err("unsupported non-RGBA png format".to_string())
         }
     }
 
+    /// Decodes a PNG file that is already in memory, e.g. one embedded with `include_bytes!`.
+    pub fn from_png_bytes(&self, bytes: &[u8]) -> Result<(), String> {
+        self.from_png(try!(png::load_png_from_memory(bytes)))
+    }
+
+    pub fn from_png_file(&self, path: &Path) -> Result<(), String> {
+        self.from_png(try!(png::load_png(path)))
+    }
+
     pub fn draw(&self, rect: Rect) {
         let nvg_image = self.nvg_image.borrow();
         let nvg_image = nvg_image.as_ref().unwrap();
 
         let Rect(Point(left, top), Point(width, height)) = rect;
+        let (flip_h, flip_v, quadrants) = self.orientation.get();
+
+        // Draw the unrotated image centered at the origin, and let the transform
+        // put it into place.
+        self.nvg.save();
+        self.nvg.translate(left + width / 2., top + height / 2.);
+        self.nvg.rotate(quadrants as f32 * f32::consts::FRAC_PI_2);
+        self.nvg.scale(if flip_h { -1. } else { 1. }, if flip_v { -1. } else { 1. });
+
+        let (width, height) = if quadrants % 2 == 1 { (height, width) } else { (width, height) };
+        let (left, top) = (-width / 2., -height / 2.);
         self.nvg.begin_path();
         self.nvg.rect(left, top, width, height);
         self.nvg.fill_paint(
             self.nvg.image_pattern(left, top, width, height, 0.0,
                                    nvg_image, nanovg::PatternRepeat::NOREPEAT, 1.0));
         self.nvg.fill();
+        self.nvg.restore();
     }
 
     pub fn draw_unscaled(&self, pos: Point) {
-        let nvg_image = self.nvg_image.borrow();
-        let nvg_image = nvg_image.as_ref().unwrap();
-
-        let (width, height) = self.nvg.image_size(nvg_image);
-        self.draw(Rect(pos, Point(width as f32, height as f32)))
+        self.draw(Rect(pos, self.oriented_size()))
     }
 
-    pub fn draw_to_fit(&self, rect: Rect) {
-        let nvg_image = self.nvg_image.borrow();
-        let nvg_image = nvg_image.as_ref().unwrap();
-
+    pub fn fit_rect(&self, rect: Rect) -> Rect {
         let Rect(Point(left, top), Point(ext_width, ext_height)) = rect;
-        let (int_width, int_height) = self.nvg.image_size(nvg_image);
-        let (int_width, int_height) = (int_width as f32, int_height as f32);
+        let Point(int_width, int_height) = self.oriented_size();
 
         let x_scale = int_width.max(ext_width) / ext_width;
         let y_scale = int_height.max(ext_height) / ext_height;
         if x_scale > y_scale {
             let offset = ext_height - (int_height / x_scale);
-            self.draw(Rect(Point(left, top + offset / 2.0),
-                           Point(int_width / x_scale, int_height / x_scale)))
+            Rect(Point(left, top + offset / 2.0),
+                 Point(int_width / x_scale, int_height / x_scale))
         } else {
             let offset = ext_width - (int_width / y_scale);
-            self.draw(Rect(Point(left + offset / 2.0, top),
-                           Point(int_width / y_scale, int_height / y_scale)))
+            Rect(Point(left + offset / 2.0, top),
+                 Point(int_width / y_scale, int_height / y_scale))
+        }
+    }
+
+    /// Maps a point drawn with `draw_to_fit(rect)` back to the pixel of the image under it.
+    /// The result is not clamped to the image bounds.
+    pub fn screen_to_image(&self, rect: Rect, point: Point) -> Point {
+        let Rect(Point(left, top), Point(width, height)) = self.fit_rect(rect);
+        let Point(oriented_width, _) = self.oriented_size();
+        let scale = width / oriented_width;
+        let (flip_h, flip_v, quadrants) = self.orientation.get();
+
+        // Undo the transform set up by `draw`, in reverse order.
+        let Point(mut x, mut y) = point - Point(left + width / 2., top + height / 2.);
+        for _ in 0..quadrants {
+            let (rx, ry) = (y, -x);
+            x = rx;
+            y = ry;
         }
+        if flip_h { x = -x }
+        if flip_v { y = -y }
+
+        let Point(image_width, image_height) = self.size();
+        Point(x / scale + image_width / 2., y / scale + image_height / 2.)
+    }
+
+    /// Maps a pixel of the image to where it is drawn with `draw_to_fit(rect)`;
+    /// the inverse of `screen_to_image`.
+    pub fn image_to_screen(&self, rect: Rect, point: Point) -> Point {
+        let Rect(Point(left, top), Point(width, height)) = self.fit_rect(rect);
+        let Point(oriented_width, _) = self.oriented_size();
+        let scale = width / oriented_width;
+        let (flip_h, flip_v, quadrants) = self.orientation.get();
+
+        // Apply the transform set up by `draw`, in the same order.
+        let Point(image_width, image_height) = self.size();
+        let Point(mut x, mut y) = point - Point(image_width / 2., image_height / 2.);
+        if flip_h { x = -x }
+        if flip_v { y = -y }
+        for _ in 0..quadrants {
+            let (rx, ry) = (-y, x);
+            x = rx;
+            y = ry;
+        }
+
+        Point(x * scale + left + width / 2., y * scale + top + height / 2.)
+    }
+
+    pub fn draw_to_fit(&self, rect: Rect) {
+        self.draw(self.fit_rect(rect))
+    }
+
+    pub fn draw_transformed(&self, rect: Rect, scale: f32, offset: Point) {
+        self.nvg.save();
+        self.nvg.translate(offset.0, offset.1);
+        self.nvg.scale(scale, scale);
+        self.draw_to_fit(rect);
+        self.nvg.restore();
     }
 }
 
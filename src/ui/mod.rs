@@ -1,29 +1,150 @@
 extern crate nanovg;
 extern crate touptek;
+extern crate glfw;
 
-use std::cell::RefCell;
+use std::cell::{Cell, RefCell};
+use std::rc::Rc;
+use std::fmt;
+use std::io::{self, Write};
 
 pub mod geometry;
 pub mod image;
 pub mod widget;
+pub mod guide;
 
-pub use ui::geometry::{Point, Rect, Direction};
+use property::Property;
+
+pub use ui::geometry::{Point, Rect, Direction, Alignment};
 pub use ui::image::Image;
-pub use ui::widget::{Widget, Container, Label, Slider, SliderPosition, BoxLayout, Frame};
+pub use ui::guide::{Guide, Crosshair, Grid, GridSpacing};
+pub use ui::widget::{Widget, Container, Label, Slider, SliderPosition, SliderScale, Button,
+                     Checkbox, RadioGroup, Dropdown, TextInput, TextEntry, Histogram,
+                     HistogramBins, Spinner, ProgressBar, Separator, Spacer, Menu, BoxLayout,
+                     GridLayout, ScrollView, Frame, Style, Theme, TextMeasurer};
 
 // Overlay
 
+const TOOLTIP_DELAY: f64 = 0.5; // seconds
+const ZOOM_STEP: f32 = 1.25;
+const ZOOM_MAX: f32 = 32.;
+const DOUBLE_CLICK_TIME: f64 = 0.3; // seconds
+const DOUBLE_CLICK_DISTANCE: f32 = 4.; // pixels
+const DRAG_THRESHOLD: f32 = 4.; // pixels
+const SELECTION_MIN_SIZE: f32 = 4.; // pixels
+const NOTIFICATION_DURATION: f64 = 5.; // seconds
+const INSET_FRACTION: f32 = 1. / 3.; // of the viewport
+const INSET_MIN_SIZE: f32 = 64.; // pixels
+
 pub struct Overlay<'nvg, 'elt> {
     nvg: &'nvg nanovg::Context,
     pub background: Image<'nvg>,
     pub frames: Vec<Frame<'nvg>>,
+    pub guides: Vec<Box<Guide + 'nvg>>,
+    menu: RefCell<Option<(Menu, &'elt Widget)>>,
+    clipboard: RefCell<Option<String>>,
+    roi: Rc<Property<Option<Rect>>>,
+    style: RefCell<Style>,
+    theme: Cell<Theme>,
+    scale: Cell<f32>,
+    reflow: Cell<bool>,
+    debug: Cell<bool>,
+    visible: Cell<bool>,
+    frame_alpha: Cell<f32>,
+    calibration: Cell<Option<f32>>,
+    // Whether anything changed since the last `draw`, and when that was.
+    dirty: Cell<bool>,
+    drawn_at: Cell<f64>,
     state: RefCell<OverlayState<'elt>>,
 }
 
 struct OverlayState<'elt> {
     mouse_at: Point,
     hovered: Option<(&'elt Widget, Point)>,
-    captured: bool,
+    captured: Option<Capture<'elt>>,
+    focused: Option<&'elt Widget>,
+    time: f64,
+    hovered_since: f64,
+    zoom: f32,
+    pan: Point,
+    panning: bool,
+    last_click: Option<(f64, Point)>,
+    viewport: Point,
+    selecting: Option<(Point, Point)>,
+    notification: Option<(String, f64)>,
+    // Where the background is drawn in picture in picture mode.
+    inset: Option<Rect>,
+    // Ends of the line being measured, in image pixels; the second one is where the mouse
+    // is until it is clicked.
+    measuring: bool,
+    measurement: Option<(Point, Option<Point>)>,
+    // Whether the color of the pixel under the mouse is shown.
+    inspecting: bool,
+    // Where the inset was grabbed, and where it was at that moment.
+    moving_inset: Option<(Point, Rect)>,
+    resizing_inset: Option<(Point, Rect)>,
+}
+
+// The widget that was pressed, and its offset at that time; it receives all mouse events
+// until the button is released, wherever the mouse moves.
+struct Capture<'elt> {
+    widget: &'elt Widget,
+    offset: Point,
+    // Where the widget was pressed, until the mouse is dragged away from there.
+    pressed_at: Option<Point>,
+}
+
+impl<'elt> Capture<'elt> {
+    // Presses `widget`, unless it is disabled; disabled widgets ignore the mouse.
+    fn new(widget: &'elt Widget, offset: Point, point: Point) -> Option<Capture<'elt>> {
+        if !widget.is_enabled() { return None }
+        widget.mouse_down(point - offset);
+        Some(Capture { widget: widget, offset: offset, pressed_at: Some(point) })
+    }
+
+    fn mouse_move(&mut self, point: Point) {
+        if let Some(pressed_at) = self.pressed_at {
+            // Small movements during a click are jitter, not a drag.
+            if point.distance(pressed_at) <= DRAG_THRESHOLD { return }
+            self.pressed_at = None
+        }
+        if self.widget.is_enabled() { self.widget.mouse_move(point - self.offset) }
+    }
+
+    // Returns whether this was a click, i.e. the mouse was not dragged.
+    fn mouse_up(self, point: Point) -> bool {
+        self.widget.mouse_up(point - self.offset);
+        if self.pressed_at.is_none() { return false }
+        self.widget.click(point - self.offset);
+        true
+    }
+
+    // Releases the widget without clicking it, e.g. because it was hidden. The release
+    // happens outside of the widget, which buttons and dropdowns take as a cancellation.
+    fn cancel(self) {
+        self.widget.mouse_up(Point(-1., -1.))
+    }
+}
+
+// Lets the widgets know that the mouse left `old` and entered `new`, unless they are
+// the same widget; returns whether they differ. Disabled widgets are still hovered, so
+// that their tooltips show, but they are not told about it, so that they don't highlight.
+fn change_hover(old: Option<&Widget>, new: Option<&Widget>) -> bool {
+    match (old, new) {
+        (None, None) => false,
+        (Some(old), Some(new)) if old.is(new) => false,
+        (old, new) => {
+            if let Some(old) = old { old.mouse_out() }
+            if let Some(new) = new {
+                if new.is_enabled() { new.mouse_in() }
+            }
+            true
+        }
+    }
+}
+
+// Disabled widgets ignore the wheel as well.
+fn scroll(widget: &Widget, offset: Point) {
+    if widget.is_enabled() { widget.mouse_scroll(offset) }
 }
 
 impl<'nvg, 'elt> Overlay<'nvg, 'elt> {
@@ -32,95 +153,768 @@ impl<'nvg, 'elt> Overlay<'nvg, 'elt> {
             nvg: nvg,
             background: Image::new(nvg),
             frames: vec![],
+            guides: vec![],
+            menu: RefCell::new(None),
+            clipboard: RefCell::new(None),
+            roi: Property::new(None),
+            style: RefCell::new(Style::new(Theme::Dark, 1.)),
+            theme: Cell::new(Theme::Dark),
+            scale: Cell::new(1.),
+            reflow: Cell::new(false),
+            debug: Cell::new(false),
+            visible: Cell::new(true),
+            frame_alpha: Cell::new(0.8),
+            calibration: Cell::new(None),
+            dirty: Cell::new(true),
+            drawn_at: Cell::new(0.),
             state: RefCell::new(OverlayState {
                 mouse_at: Point(0., 0.),
                 hovered: None,
-                captured: false,
+                captured: None,
+                focused: None,
+                time: 0.,
+                hovered_since: 0.,
+                zoom: 1.,
+                pan: Point(0., 0.),
+                panning: false,
+                last_click: None,
+                viewport: Point(0., 0.),
+                selecting: None,
+                notification: None,
+                inset: None,
+                measuring: false,
+                measurement: None,
+                inspecting: false,
+                moving_inset: None,
+                resizing_inset: None,
             }),
         }
     }
 
-    pub fn prepare(&self) {
+    fn restyle(&self) {
+        *self.style.borrow_mut() = Style::new(self.theme.get(), self.scale.get())
+    }
+
+    pub fn set_theme(&self, theme: Theme) {
+        self.theme.set(theme);
+        self.restyle()
+    }
+
+    pub fn scale(&self) -> f32 { self.scale.get() }
+
+    /// Multiplies all sizes in the style by `scale`, and lays the frames out anew.
+    pub fn set_scale(&self, scale: f32) {
+        if scale == self.scale.get() { return }
+        self.scale.set(scale);
+        self.restyle();
+        self.reflow.set(true)
+    }
+
+    /// Enables printing of event dispatch diagnostics to stderr.
+    pub fn set_debug(&self, debug: bool) {
+        self.debug.set(debug)
+    }
+
+    pub fn frame_alpha(&self) -> f32 { self.frame_alpha.get() }
+
+    /// Sets the opacity of the frames over the background, from 0 to 1.
+    pub fn set_frame_alpha(&self, alpha: f32) {
+        self.frame_alpha.set(alpha.max(0.).min(1.))
+    }
+
+    pub fn visible(&self) -> bool { self.visible.get() }
+
+    /// Shows or hides the frames; the background is always shown. Hidden frames don't
+    /// receive any input, and lose hover and focus.
+    pub fn set_visible(&self, visible: bool) {
+        if visible == self.visible.get() { return }
+        self.visible.set(visible);
+        if visible { return }
+
+        *self.menu.borrow_mut() = None;
+        let mut state = self.state.borrow_mut();
+        if let Some(capture) = state.captured.take() {
+            capture.cancel()
+        }
+        if let Some((widget, _)) = state.hovered.take() {
+            widget.mouse_out()
+        }
+        if let Some(widget) = state.focused.take() {
+            widget.focus_out()
+        }
+    }
+
+    pub fn measuring(&self) -> bool {
+        self.state.borrow().measuring
+    }
+
+    /// In measuring mode, clicking on two points of the background shows the distance
+    /// between them, instead of selecting a region.
+    pub fn set_measuring(&self, measuring: bool) {
+        let mut state = self.state.borrow_mut();
+        state.measuring = measuring;
+        state.measurement = None
+    }
+
+    pub fn inspecting(&self) -> bool {
+        self.state.borrow().inspecting
+    }
+
+    /// Shows the color of the camera image pixel under the mouse next to it.
+    pub fn set_inspecting(&self, inspecting: bool) {
+        self.state.borrow_mut().inspecting = inspecting
+    }
+
+    /// Sets how many image pixels make up a millimeter, so that measurements can be shown
+    /// in physical units as well.
+    pub fn set_calibration(&self, pixels_per_mm: Option<f32>) {
+        self.calibration.set(pixels_per_mm)
+    }
+
+    pub fn picture_in_picture(&self) -> bool {
+        self.state.borrow().inset.is_some()
+    }
+
+    /// Draws the background into a small inset in the corner of the window, above the
+    /// frames, instead of behind them. The inset can be moved by its edge, and resized
+    /// by its corner.
+    pub fn set_picture_in_picture(&self, enabled: bool) {
+        let mut state = self.state.borrow_mut();
+        state.inset = if enabled {
+            let margin = widget::inset_border_size(&self.style.borrow()) * 2.;
+            let size = (state.viewport * INSET_FRACTION).round();
+            Some(Rect(state.viewport - size - Point(margin, margin), size))
+        } else {
+            None
+        };
+        state.moving_inset = None;
+        state.resizing_inset = None
+    }
+
+    // Where the background is drawn.
+    fn background_rect(state: &OverlayState) -> Rect {
+        state.inset.unwrap_or(state.viewport.as_rect())
+    }
+
+    fn log(&self, args: fmt::Arguments) {
+        if self.debug.get() {
+            let _ = writeln!(io::stderr(), "ui: {}", args);
+        }
+    }
+
+    pub fn prepare(&self, time: f64) {
+        self.state.borrow_mut().time = time;
+        // Hidden frames are laid out once they are shown again.
+        if !self.visible.get() { return }
+
+        let style = self.style.borrow();
+        let reflow = self.reflow.get();
+        self.reflow.set(false);
         for frame in &self.frames {
-            frame.prepare();
+            frame.prepare(time);
+
+            if reflow || frame.need_reflow(&style, self.nvg) {
+                let size = frame.size_request(&style, self.nvg);
+                frame.set_size(size, &style, self.nvg);
+                self.dirty.set(true)
+            }
 
-            if frame.need_reflow() {
-                let size = frame.size_request();
-                frame.set_size(size);
+            // The viewport is only known once something has been drawn.
+            let viewport = self.state.borrow().viewport;
+            if viewport != Point(0., 0.) {
+                frame.clamp_position(viewport, &style)
             }
         }
     }
 
+    /// The region of the background selected by dragging over it, in image pixels.
+    pub fn roi(&self) -> Rc<Property<Option<Rect>>> { self.roi.clone() }
+
+    // Inverse of the transform applied by `draw_transformed`.
+    fn screen_to_image(&self, point: Point) -> Point {
+        self.screen_to_image_in(&self.state.borrow(), point)
+    }
+
+    fn screen_to_image_in(&self, state: &OverlayState, point: Point) -> Point {
+        self.background.screen_to_image(Overlay::background_rect(state),
+                                        (point - state.pan) * (1. / state.zoom))
+    }
+
+    /// Makes `needs_redraw` return true until the next `draw`; anything that changes what
+    /// is shown, like input or a new camera frame, has to call this.
+    pub fn invalidate(&self) {
+        self.dirty.set(true)
+    }
+
+    /// Whether drawing again would show anything different, as of the last `prepare`.
+    pub fn needs_redraw(&self) -> bool {
+        if self.dirty.get() { return true }
+        if self.visible.get() && self.frames.iter().any(|frame| frame.animating()) {
+            return true
+        }
+
+        // Tooltips and notifications come and go by themselves after a while.
+        let state = self.state.borrow();
+        let due = |at: f64| self.drawn_at.get() <= at && at < state.time;
+        let tooltip_due = match (state.captured.is_some(), state.hovered) {
+            (false, Some(_)) => due(state.hovered_since + TOOLTIP_DELAY),
+            _ => false
+        };
+        let notification_due = match state.notification {
+            Some((_, since)) => due(since + NOTIFICATION_DURATION),
+            None => false
+        };
+        tooltip_due || notification_due
+    }
+
     pub fn draw(&self, size: Point) {
+        let style = self.style.borrow();
+        self.state.borrow_mut().viewport = size;
+        self.dirty.set(false);
+        self.drawn_at.set(self.state.borrow().time);
+
+        let inset = self.state.borrow().inset;
+        if inset.is_none() {
+            self.draw_background()
+        }
+
+        if self.visible.get() {
+            self.nvg.save();
+            self.nvg.global_alpha(self.frame_alpha.get());
+            for frame in &self.frames {
+                frame.render(self.nvg, &style)
+            }
+            self.nvg.restore();
+        }
+
+        if let Some(rect) = inset {
+            let Rect(Point(left, top), Point(width, height)) = rect;
+            self.nvg.save();
+            self.nvg.intersect_scissor(left, top, width, height);
+            self.draw_background();
+            self.nvg.restore();
+            widget::draw_inset_border(self.nvg, &style, rect)
+        }
+
+        if let Some((ref text, since)) = self.state.borrow().notification {
+            if self.state.borrow().time - since < NOTIFICATION_DURATION {
+                widget::draw_banner(self.nvg, &style, size, text)
+            }
+        }
+
+        self.draw_pixel_info();
+
+        if let Some((ref menu, _)) = *self.menu.borrow() {
+            let Point(x, y) = menu.position();
+            self.nvg.save();
+            self.nvg.translate(x, y);
+            menu.render(self.nvg, &style);
+            self.nvg.restore();
+            return
+        }
+
+        let state = self.state.borrow();
+        if let (false, Some((widget, _))) = (state.captured.is_some(), state.hovered) {
+            if state.time - state.hovered_since > TOOLTIP_DELAY {
+                if let Some(tooltip) = widget.tooltip() {
+                    widget::draw_tooltip(self.nvg, &style, state.mouse_at, &tooltip)
+                }
+            }
+        }
+    }
+
+    fn draw_background(&self) {
+        let (state, style) = (self.state.borrow(), self.style.borrow());
         if self.background.present() {
-            self.background.draw_to_fit(size.as_rect())
+            let rect = Overlay::background_rect(&state);
+            self.background.draw_transformed(rect, state.zoom, state.pan);
+
+            // Guides follow the image when it is zoomed, but their lines keep their width.
+            let Rect(origin, size) = self.background.fit_rect(rect);
+            let image = Rect(state.pan + origin * state.zoom, size * state.zoom);
+            let pixels = self.background.oriented_size();
+            for guide in &self.guides {
+                guide.draw(self.nvg, image, pixels)
+            }
         }
 
-        self.nvg.save();
-        self.nvg.global_alpha(0.8);
-        for frame in &self.frames {
-            frame.render()
+        if let Some((start, end)) = state.selecting {
+            widget::draw_selection(self.nvg, &style, Rect::from_corners(start, end))
+        }
+
+        if let (true, Some((start, end))) = (self.background.present(), state.measurement) {
+            let rect = Overlay::background_rect(&state);
+            let to_screen = |point| state.pan + self.background.image_to_screen(rect, point) *
+                                                state.zoom;
+            let (start_at, end_at) = match end {
+                Some(end) => (to_screen(start), to_screen(end)),
+                None => (to_screen(start), state.mouse_at)
+            };
+            let end = end.unwrap_or(self.screen_to_image_in(&state, state.mouse_at));
+
+            let distance = start.distance(end);
+            let text = match self.calibration.get() {
+                Some(pixels_per_mm) =>
+                    format!("{:.1} px, {:.3} mm", distance, distance / pixels_per_mm),
+                None => format!("{:.1} px", distance)
+            };
+            widget::draw_measurement(self.nvg, &style, start_at, end_at, &text)
+        }
+    }
+
+    fn draw_pixel_info(&self) {
+        let state = self.state.borrow();
+        if !state.inspecting || state.hovered.is_some() || state.captured.is_some() ||
+                state.selecting.is_some() || !self.background.present() ||
+                !Overlay::background_rect(&state).contains(state.mouse_at) {
+            return
+        }
+
+        let point = self.screen_to_image_in(&state, state.mouse_at);
+        if let Some(rgba) = self.background.pixel(point) {
+            widget::draw_pixel_info(self.nvg, &self.style.borrow(), state.mouse_at, rgba)
         }
-        self.nvg.restore();
+    }
+
+    /// Takes the text last copied from a context menu, which is to be put on the clipboard.
+    pub fn take_clipboard(&self) -> Option<String> {
+        self.clipboard.borrow_mut().take()
+    }
+
+    /// Shows `text` on top of everything else for a few seconds.
+    pub fn notify(&self, text: String) {
+        let mut state = self.state.borrow_mut();
+        state.notification = Some((text, state.time));
+        self.dirty.set(true)
     }
 
     pub fn mouse_move(&'elt self, point: Point) {
+        if let Some((ref menu, _)) = *self.menu.borrow() {
+            // An open menu captures the mouse until it is dismissed.
+            self.state.borrow_mut().mouse_at = point;
+            menu.mouse_move(point - menu.position());
+            return
+        }
+
         let mut state = self.state.borrow_mut();
 
-        if !state.captured {
-            let mut new_hovered = None;
-            for frame in &self.frames {
-                if let Some((widget, proj_point)) = frame.project(point) {
-                    new_hovered = Some((widget, point - proj_point));
-                    break
-                }
-            }
+        if state.panning {
+            state.pan = state.pan + (point - state.mouse_at);
+            state.mouse_at = point;
+            return
+        }
 
-            match (state.hovered, new_hovered) {
-                (None, None) => (),
-                (Some((widget, _)), None) => {
-                    widget.mouse_out()
-                },
-                (None, Some((widget, _))) => {
-                    widget.mouse_in()
-                },
-                (Some((old_widget, _)), Some((new_widget, _))) => {
-                    if !old_widget.is(new_widget) {
-                        old_widget.mouse_out();
-                        new_widget.mouse_in()
-                    }
-                }
-            };
+        if let Some((start, _)) = state.selecting {
+            state.selecting = Some((start, point));
+            state.mouse_at = point;
+            return
+        }
+
+        if let Some((grab, Rect(origin, size))) = state.moving_inset {
+            state.inset = Some(Rect((origin + point - grab).round(), size));
+            state.mouse_at = point;
+            return
+        }
 
-            state.hovered = new_hovered
+        if let Some((grab, Rect(origin, size))) = state.resizing_inset {
+            let Point(width, height) = (size + point - grab).round();
+            state.inset = Some(Rect(origin, Point(width.max(INSET_MIN_SIZE),
+                                                  height.max(INSET_MIN_SIZE))));
+            state.mouse_at = point;
+            return
+        }
+
+        if state.captured.is_none() {
+            self.update_hovered(&mut state, point)
         }
 
         state.mouse_at = point;
+        if let Some(ref mut capture) = state.captured {
+            self.log(format_args!("mouse move at {:?}, offset {:?}", point, capture.offset));
+            capture.mouse_move(point);
+            return
+        }
         if let Some((widget, offset)) = state.hovered {
+            if !widget.is_enabled() { return }
+            self.log(format_args!("mouse move at {:?}, offset {:?}", point, offset));
             widget.mouse_move(state.mouse_at - offset);
         }
     }
 
+    fn update_hovered(&'elt self, state: &mut OverlayState<'elt>, point: Point) {
+        let mut new_hovered = None;
+        // The inset is on top of the frames.
+        let over_inset = state.inset.map_or(false, |inset| inset.contains(point));
+        let frames: &[Frame] = if self.visible.get() && !over_inset { &self.frames } else { &[] };
+        for frame in frames {
+            if let Some((widget, proj_point)) = frame.project(point) {
+                new_hovered = Some((widget, point - proj_point));
+                break
+            }
+        }
+
+        if change_hover(state.hovered.map(|(widget, _)| widget),
+                        new_hovered.map(|(widget, _)| widget)) {
+            state.hovered_since = state.time
+        }
+        state.hovered = new_hovered
+    }
+
     pub fn mouse_scroll(&self, offset: Point) {
-        let state = self.state.borrow();
-        if let Some((widget, _)) = state.hovered {
-            widget.mouse_scroll(offset)
+        let mut state = self.state.borrow_mut();
+        match state.hovered {
+            Some((widget, _)) => scroll(widget, offset),
+            None => {
+                // Zoom the background around the cursor, keeping the point under it in place.
+                let zoom = (state.zoom * ZOOM_STEP.powf(offset.1)).max(1.).min(ZOOM_MAX);
+                if zoom == 1. {
+                    state.pan = Point(0., 0.)
+                } else {
+                    state.pan = state.mouse_at - (state.mouse_at - state.pan) * (zoom / state.zoom)
+                }
+                state.zoom = zoom
+            }
         }
     }
 
-    pub fn mouse_down(&self) {
+    pub fn pan_begin(&self) {
+        self.state.borrow_mut().panning = true
+    }
+
+    pub fn pan_end(&self) {
+        self.state.borrow_mut().panning = false
+    }
+
+    // An open menu keeps mouse moves to itself, so what is hovered has to be found again.
+    fn close_menu(&'elt self) {
+        let mut state = self.state.borrow_mut();
+        let mouse_at = state.mouse_at;
+        self.update_hovered(&mut state, mouse_at)
+    }
+
+    pub fn mouse_down(&'elt self) {
+        let menu = self.menu.borrow_mut().take();
+        if let Some((menu, target)) = menu {
+            let mouse_at = self.state.borrow().mouse_at;
+            match menu.item_at(mouse_at - menu.position()) {
+                Some(index) if index < target.context_menu().len() =>
+                    target.context_action(index),
+                Some(_) => *self.clipboard.borrow_mut() = target.copy_text(),
+                None => ()
+            }
+            self.close_menu();
+            return
+        }
+
         let mut state = self.state.borrow_mut();
+        if let Some(inset) = state.inset {
+            let Rect(origin, size) = inset;
+            let border = widget::inset_border_size(&self.style.borrow());
+            let inner = Rect(origin + Point(border, border), size - Point(border, border) * 2.);
+            let grip = Rect(origin + size - Point(border, border), Point(border, border));
+            if grip.contains(state.mouse_at) {
+                state.resizing_inset = Some((state.mouse_at, inset));
+                return
+            } else if inset.contains(state.mouse_at) && !inner.contains(state.mouse_at) {
+                state.moving_inset = Some((state.mouse_at, inset));
+                return
+            }
+        }
+
+        let background_rect = Overlay::background_rect(&state);
+        if state.hovered.is_none() && self.background.present() &&
+                background_rect.contains(state.mouse_at) {
+            if state.measuring {
+                let point = self.screen_to_image_in(&state, state.mouse_at);
+                state.measurement = match state.measurement {
+                    Some((start, None)) => Some((start, Some(point))),
+                    _ => Some((point, None))
+                };
+            } else {
+                state.selecting = Some((state.mouse_at, state.mouse_at));
+            }
+            return
+        }
+
         if let Some((widget, offset)) = state.hovered {
-            state.captured = true;
-            widget.mouse_down(state.mouse_at - offset)
+            let capture = match Capture::new(widget, offset, state.mouse_at) {
+                Some(capture) => capture,
+                None => return
+            };
+            self.log(format_args!("mouse down at {:?}, offset {:?}", state.mouse_at, offset));
+            state.captured = Some(capture);
+            if widget.accepts_focus() && !state.focused.map_or(false, |f| f.is(widget)) {
+                if let Some(focused) = state.focused { focused.focus_out() }
+                widget.focus_in();
+                state.focused = Some(widget)
+            }
+
+            let double_click = match state.last_click {
+                Some((time, point)) =>
+                    state.time - time < DOUBLE_CLICK_TIME &&
+                        state.mouse_at.distance(point) < DOUBLE_CLICK_DISTANCE,
+                None => false
+            };
+            if double_click {
+                widget.double_click(state.mouse_at - offset);
+                state.last_click = None
+            } else {
+                state.last_click = Some((state.time, state.mouse_at))
+            }
+        }
+    }
+
+    pub fn mouse_down_right(&'elt self) {
+        let menu = self.menu.borrow_mut().take();
+        if menu.is_some() {
+            self.close_menu();
+            return
+        }
+
+        let state = self.state.borrow();
+        if let (false, Some((widget, _))) = (state.captured.is_some(), state.hovered) {
+            if !widget.is_enabled() { return }
+            let mut items = widget.context_menu();
+            if widget.copy_text().is_some() { items.push("Copy value".to_string()) }
+            if items.is_empty() { return }
+
+            let menu = Menu::new(items, state.mouse_at);
+            let style = self.style.borrow();
+            menu.set_size(menu.size_request(&style, self.nvg), &style, self.nvg);
+            *self.menu.borrow_mut() = Some((menu, widget))
         }
     }
 
-    pub fn mouse_up(&self) {
+    pub fn mouse_up(&'elt self) {
+        {
+            let mut state = self.state.borrow_mut();
+            if state.moving_inset.take().is_some() || state.resizing_inset.take().is_some() {
+                return
+            }
+        }
+
+        let selecting = self.state.borrow_mut().selecting.take();
+        if let Some((start, end)) = selecting {
+            // A click, or a drag too short or too thin to be meant as a selection,
+            // leaves the region of interest as it is.
+            let Point(dx, dy) = end - start;
+            if dx.abs() < SELECTION_MIN_SIZE || dy.abs() < SELECTION_MIN_SIZE { return }
+
+            let selection = Rect::from_corners(self.screen_to_image(start),
+                                               self.screen_to_image(end));
+            let image = self.background.size().as_rect();
+            if let Some(Rect(origin, size)) = selection.intersect(image) {
+                self.roi.set(Some(Rect(origin.round(), size.round())))
+            }
+            return
+        }
+
         let mut state = self.state.borrow_mut();
-        if let Some((widget, offset)) = state.hovered {
-            state.captured = false;
-            widget.mouse_up(state.mouse_at - offset)
+        if let Some(capture) = state.captured.take() {
+            let offset = capture.offset;
+            if capture.mouse_up(state.mouse_at) {
+                self.log(format_args!("click at {:?}, offset {:?}", state.mouse_at, offset))
+            }
+
+            // Hovering was not tracked during the drag, and the widget under the mouse
+            // may have changed, e.g. because a frame was moved from under it.
+            let mouse_at = state.mouse_at;
+            self.update_hovered(&mut state, mouse_at)
         }
     }
+
+    fn focusable(&'elt self) -> Vec<&'elt Widget> {
+        fn collect<'a>(widget: &'a Widget, focusable: &mut Vec<&'a Widget>) {
+            if widget.accepts_focus() && widget.is_enabled() { focusable.push(widget) }
+            for child in widget.children() { collect(child, focusable) }
+        }
+
+        let mut focusable = Vec::new();
+        if !self.visible.get() { return focusable }
+        for frame in &self.frames {
+            collect(frame, &mut focusable)
+        }
+        focusable
+    }
+
+    fn move_focus(&'elt self, forward: bool) {
+        let focusable = self.focusable();
+        if focusable.is_empty() { return }
+
+        let mut state = self.state.borrow_mut();
+        let current = state.focused.and_then(|focused|
+            focusable.iter().position(|widget| widget.is(focused)));
+        let index = match (current, forward) {
+            (None, true) => 0,
+            (None, false) => focusable.len() - 1,
+            (Some(index), true) => (index + 1) % focusable.len(),
+            (Some(index), false) => (index + focusable.len() - 1) % focusable.len(),
+        };
+
+        if let Some(widget) = state.focused {
+            widget.focus_out()
+        }
+        focusable[index].focus_in();
+        state.focused = Some(focusable[index])
+    }
+
+    pub fn focus_next(&'elt self) {
+        self.move_focus(true)
+    }
+
+    pub fn focus_prev(&'elt self) {
+        self.move_focus(false)
+    }
+
+    pub fn key_input(&self, key: glfw::Key, modifiers: glfw::Modifiers) {
+        let state = self.state.borrow();
+        if let Some(widget) = state.focused {
+            widget.key_input(key, modifiers)
+        }
+    }
+
+    pub fn char_input(&self, character: char) {
+        let state = self.state.borrow();
+        if let Some(widget) = state.focused {
+            widget.char_input(character)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::{Cell, RefCell};
+    use super::nanovg;
+    use super::{Capture, Point, Style, Theme, TextMeasurer, Widget, Button, Slider,
+                SliderPosition};
+    use super::{change_hover, scroll};
+    use super::widget::State;
+
+    // Records the mouse events it receives, in its own coordinates.
+    struct Recorder {
+        events: RefCell<Vec<(&'static str, Point)>>,
+        enabled: Cell<bool>,
+    }
+
+    impl Recorder {
+        fn new() -> Recorder {
+            Recorder { events: RefCell::new(Vec::new()), enabled: Cell::new(true) }
+        }
+
+        fn record(&self, event: &'static str, point: Point) {
+            self.events.borrow_mut().push((event, point))
+        }
+    }
+
+    impl Widget for Recorder {
+        fn size(&self) -> Point { Point(10., 10.) }
+        fn set_size(&self, _size: Point, _style: &Style, _measurer: &TextMeasurer) {}
+        fn size_request(&self, _style: &Style, _measurer: &TextMeasurer) -> Point {
+            Point(10., 10.)
+        }
+        fn render(&self, _nvg: &nanovg::Context, _style: &Style) {}
+
+        fn mouse_down(&self, point: Point) { self.record("down", point) }
+        fn mouse_move(&self, point: Point) { self.record("move", point) }
+        fn mouse_up(&self, point: Point) { self.record("up", point) }
+        fn click(&self, point: Point) { self.record("click", point) }
+        fn mouse_scroll(&self, offset: Point) { self.record("scroll", offset) }
+
+        fn is_enabled(&self) -> bool { self.enabled.get() }
+        fn set_enabled(&self, enabled: bool) { self.enabled.set(enabled) }
+    }
+
+    #[test]
+    fn drag_follows_captured_widget_out_of_its_bounds() {
+        let widget = Recorder::new();
+        let mut capture = Capture::new(&widget, Point(100., 100.), Point(105., 105.)).unwrap();
+        capture.mouse_move(Point(300., 50.));
+        capture.mouse_move(Point(20., 400.));
+        assert!(!capture.mouse_up(Point(20., 400.)));
+        assert_eq!(*widget.events.borrow(),
+                   vec![("down", Point(5., 5.)), ("move", Point(200., -50.)),
+                        ("move", Point(-80., 300.)), ("up", Point(-80., 300.))]);
+    }
+
+    #[test]
+    fn jitter_during_press_is_a_click() {
+        let widget = Recorder::new();
+        let mut capture = Capture::new(&widget, Point(100., 100.), Point(105., 105.)).unwrap();
+        capture.mouse_move(Point(106., 106.));
+        assert!(capture.mouse_up(Point(106., 106.)));
+        assert_eq!(*widget.events.borrow(),
+                   vec![("down", Point(5., 5.)), ("up", Point(6., 6.)), ("click", Point(6., 6.))]);
+    }
+
+    #[test]
+    fn slider_is_rehovered_after_drag() {
+        let slider = Slider::new(SliderPosition {
+            current: 0., minimum: 0., maximum: 100., step: 1.
+        });
+
+        assert!(change_hover(None, Some(&slider as &Widget)));
+        assert_eq!(slider.ui_state(), State::Hovered);
+
+        let mut capture = Capture::new(&slider, Point(0., 0.), Point(100., 15.)).unwrap();
+        assert_eq!(slider.ui_state(), State::Active);
+        capture.mouse_move(Point(100., 200.));
+        assert_eq!(slider.ui_state(), State::Active);
+        capture.mouse_up(Point(100., 200.));
+        assert_eq!(slider.ui_state(), State::Hovered);
+
+        // The drag ended away from the slider, so it must not stay highlighted.
+        assert!(change_hover(Some(&slider as &Widget), None));
+        assert_eq!(slider.ui_state(), State::Passive);
+    }
+
+    #[test]
+    fn disabled_widget_ignores_mouse() {
+        let widget = Recorder::new();
+        widget.set_enabled(false);
+        assert!(Capture::new(&widget, Point(0., 0.), Point(5., 5.)).is_none());
+        scroll(&widget, Point(0., 1.));
+        assert!(widget.events.borrow().is_empty());
+
+        widget.set_enabled(true);
+        scroll(&widget, Point(0., 1.));
+        assert_eq!(*widget.events.borrow(), vec![("scroll", Point(0., 1.))]);
+    }
+
+    // Measures all text as empty; enough for widgets whose size is set directly.
+    struct NoText;
+
+    impl TextMeasurer for NoText {
+        fn text_size(&self, _style: &Style, _text: &str) -> Point { Point(0., 0.) }
+        fn text_box_size(&self, _style: &Style, _width: f32, _text: &str) -> Point {
+            Point(0., 0.)
+        }
+    }
+
+    // A 100 by 30 pixel button that counts its clicks.
+    fn button<'a>(clicks: &'a Cell<u32>) -> Button<'a> {
+        let button = Button::new("OK".to_string());
+        button.on_click(move || clicks.set(clicks.get() + 1));
+        button.set_size(Point(100., 30.), &Style::new(Theme::Dark, 1.), &NoText);
+        button
+    }
+
+    #[test]
+    fn release_just_outside_button_does_not_click() {
+        let clicks = Cell::new(0);
+        let button = button(&clicks);
+        let mut capture = Capture::new(&button, Point(10., 10.), Point(108., 38.)).unwrap();
+        capture.mouse_move(Point(111., 39.));
+        capture.mouse_up(Point(111., 39.));
+        assert_eq!(clicks.get(), 0);
+    }
+
+    #[test]
+    fn move_inside_button_then_release_clicks() {
+        let clicks = Cell::new(0);
+        let button = button(&clicks);
+        let mut capture = Capture::new(&button, Point(10., 10.), Point(20., 20.)).unwrap();
+        capture.mouse_move(Point(80., 25.));
+        capture.mouse_up(Point(80., 25.));
+        assert_eq!(clicks.get(), 1);
+    }
 }
@@ -1,12 +1,16 @@
 #![allow(dead_code)]
 
 extern crate nanovg;
+extern crate glfw;
 
-use std::cell::RefCell;
+use std::cell::{Cell, RefCell};
+use std::cmp;
+use std::f32;
+use std::f64;
 use std::rc::Rc;
 
 use property::Property;
-use ui::{Point, Rect, Direction};
+use ui::{Point, Rect, Direction, Alignment};
 
 // Generic
 
@@ -17,31 +21,68 @@ pub enum State {
     Active,
 }
 
+/// Eases from 0 to 1 as `t` goes from 0 to 1; fast at first, and slowing down towards
+/// the end.
+pub fn ease_out(t: f32) -> f32 {
+    let t = t.max(0.).min(1.);
+    1. - (1. - t).powi(3)
+}
+
 pub trait Widget {
     fn is(&self, other: &Widget) -> bool {
         (self as *const _ as *const ()) == (other as *const _ as *const ())
     }
 
     fn size(&self) -> Point;
-    fn set_size(&self, size: Point);
-    fn size_request(&self) -> Point;
-
-    fn prepare(&self) {}
-    fn need_reflow(&self) -> bool {
-        let Point(rw, rh) = self.size_request();
+    fn set_size(&self, size: Point, style: &Style, measurer: &TextMeasurer);
+    fn size_request(&self, style: &Style, measurer: &TextMeasurer) -> Point;
+    fn min_size(&self, _style: &Style) -> Option<Point> { None }
+    fn max_size(&self, _style: &Style) -> Option<Point> { None }
+
+    /// Called once per frame before layout; `time` is in seconds, for animation.
+    fn prepare(&self, _time: f64) {}
+    /// Whether the widget changes by itself over time, and has to be drawn again on
+    /// the next frame even if nothing else happens.
+    fn animating(&self) -> bool { false }
+
+    fn need_reflow(&self, style: &Style, measurer: &TextMeasurer) -> bool {
+        let Point(rw, rh) = self.size_request(style, measurer);
         let Point(aw, ah) = self.size();
         rw > aw || rh > ah
     }
 
-    fn render(&self);
+    fn render(&self, nvg: &nanovg::Context, style: &Style);
+
+    fn children<'a>(&'a self) -> Vec<&'a Widget> { Vec::new() }
 
     fn project(&self, _point: Point) -> Option<(&Widget, Point)> { None }
     fn mouse_move(&self, _point: Point) {}
     fn mouse_scroll(&self, _offset: Point) {}
     fn mouse_down(&self, _point: Point) {}
     fn mouse_up(&self, _point: Point) {}
+    /// Called after `mouse_up` if the mouse has not been dragged since `mouse_down`.
+    fn click(&self, _point: Point) {}
+    fn double_click(&self, _point: Point) {}
     fn mouse_in(&self) {}
     fn mouse_out(&self) {}
+
+    fn accepts_focus(&self) -> bool { false }
+    fn focus_in(&self) {}
+    fn focus_out(&self) {}
+    fn key_input(&self, _key: glfw::Key, _modifiers: glfw::Modifiers) {}
+    fn char_input(&self, _character: char) {}
+
+    fn tooltip(&self) -> Option<String> { None }
+
+    /// Whether the widget reacts to input; a disabled widget is drawn grayed out.
+    fn is_enabled(&self) -> bool { true }
+    /// Widgets that take no input ignore this, and layouts pass it on to their children.
+    fn set_enabled(&self, _enabled: bool) {}
+
+    fn context_menu(&self) -> Vec<String> { Vec::new() }
+    fn context_action(&self, _index: usize) {}
+    /// Text that "Copy value", added to the end of the context menu, puts on the clipboard.
+    fn copy_text(&self) -> Option<String> { None }
 }
 
 pub trait Container<'nvg> {
@@ -74,81 +115,301 @@ pub struct Style {
     active_color: nanovg::Color,
     hover_color: nanovg::Color,
     passive_color: nanovg::Color,
+    disabled_color: nanovg::Color,
     background_color: nanovg::Color,
+    error_color: nanovg::Color,
 
     // Sizes
     line_size: f32,
     frame_corner_size: f32,
 }
 
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Theme {
+    Dark,
+    Light
+}
+
 impl Style {
-    fn get() -> &'static Style {
-        static STYLE: Style = Style {
-            font_face: "Roboto",
-            font_size: 28.,
-            passive_color: nanovg::Color::rgb_f(0.5, 0.5, 0.5),
-            hover_color: nanovg::Color::rgb_f(1., 0.5, 0.),
-            active_color: nanovg::Color::rgb_f(1., 1., 1.),
-            background_color: nanovg::Color::rgb_f(0.15, 0.15, 0.15),
-            line_size: 4.,
-            frame_corner_size: 10.,
+    /// Creates a style with the given color theme, and all sizes multiplied by `scale`.
+    pub fn new(theme: Theme, scale: f32) -> Style {
+        let style = match theme {
+            Theme::Dark => Style {
+                font_face: "Roboto",
+                font_size: 28.,
+                passive_color: nanovg::Color::rgb_f(0.5, 0.5, 0.5),
+                disabled_color: nanovg::Color::rgb_f(0.35, 0.35, 0.35),
+                hover_color: nanovg::Color::rgb_f(1., 0.5, 0.),
+                active_color: nanovg::Color::rgb_f(1., 1., 1.),
+                background_color: nanovg::Color::rgb_f(0.15, 0.15, 0.15),
+                error_color: nanovg::Color::rgb_f(1., 0.2, 0.2),
+                line_size: 4.,
+                frame_corner_size: 10.,
+            },
+            Theme::Light => Style {
+                font_face: "Roboto",
+                font_size: 28.,
+                passive_color: nanovg::Color::rgb_f(0.6, 0.6, 0.6),
+                disabled_color: nanovg::Color::rgb_f(0.78, 0.78, 0.78),
+                hover_color: nanovg::Color::rgb_f(0.9, 0.4, 0.),
+                active_color: nanovg::Color::rgb_f(0.1, 0.1, 0.1),
+                background_color: nanovg::Color::rgb_f(0.92, 0.92, 0.92),
+                error_color: nanovg::Color::rgb_f(0.8, 0.1, 0.1),
+                line_size: 4.,
+                frame_corner_size: 10.,
+            },
         };
 
-        return &STYLE;
+        Style {
+            font_size: style.font_size * scale,
+            line_size: style.line_size * scale,
+            frame_corner_size: style.frame_corner_size * scale,
+            ..style
+        }
+    }
+}
+
+// Text metrics
+
+/// Measures text set in `style`. This is all that layout needs of a nanovg context,
+/// so layout can be computed with another implementation, without GL.
+pub trait TextMeasurer {
+    fn text_size(&self, style: &Style, text: &str) -> Point;
+    /// Size of `text` wrapped to `width`.
+    fn text_box_size(&self, style: &Style, width: f32, text: &str) -> Point;
+}
+
+impl TextMeasurer for nanovg::Context {
+    fn text_size(&self, style: &Style, text: &str) -> Point {
+        self.font_face(&style.font_face);
+        self.font_size(style.font_size);
+
+        let mut bounds = [0.; 4];
+        self.text_bounds(0., 0., text, &mut bounds);
+        Point(bounds[2] - bounds[0], bounds[3] - bounds[1])
+    }
+
+    fn text_box_size(&self, style: &Style, width: f32, text: &str) -> Point {
+        self.font_face(&style.font_face);
+        self.font_size(style.font_size);
+        self.text_align(nanovg::LEFT | nanovg::TOP);
+
+        let mut bounds = [0.; 4];
+        self.text_box_bounds(0., 0., width, text, &mut bounds);
+        Point(bounds[2] - bounds[0], bounds[3] - bounds[1])
+    }
+}
+
+// Tooltip
+
+pub fn draw_tooltip(nvg: &nanovg::Context, style: &Style, point: Point, text: &str) {
+    let padding = style.font_size / 4.;
+
+    let Point(w, h) = nvg.text_size(style, text) + Point(padding, padding) * 2.;
+
+    // Place the tooltip below and to the right of the cursor, clear of the pointer itself.
+    let Point(x, y) = (point + Point(style.font_size / 2., style.font_size)).round();
+    nvg.begin_path();
+    nvg.rounded_rect(x, y, w, h, style.frame_corner_size / 2.);
+    nvg.fill_color(style.background_color);
+    nvg.fill();
+    nvg.stroke_width(style.line_size / 2.);
+    nvg.stroke_color(style.passive_color);
+    nvg.stroke();
+
+    nvg.fill_color(style.active_color);
+    nvg.text_align(nanovg::LEFT | nanovg::TOP);
+    nvg.text(x + padding, y + padding, text);
+}
+
+// Banner
+
+pub fn draw_banner(nvg: &nanovg::Context, style: &Style, viewport: Point, text: &str) {
+    let padding = style.font_size / 2.;
+
+    let Point(w, h) = nvg.text_size(style, text) + Point(padding, padding) * 2.;
+
+    // Centered at the top of the viewport.
+    let Point(x, y) = Point((viewport.0 - w) / 2., padding).round();
+    nvg.begin_path();
+    nvg.rounded_rect(x, y, w, h, style.frame_corner_size);
+    nvg.fill_color(style.background_color);
+    nvg.fill();
+    nvg.stroke_width(style.line_size / 2.);
+    nvg.stroke_color(style.hover_color);
+    nvg.stroke();
+
+    nvg.fill_color(style.active_color);
+    nvg.text_align(nanovg::LEFT | nanovg::TOP);
+    nvg.text(x + padding, y + padding, text);
+}
+
+// Selection
+
+pub fn draw_selection(nvg: &nanovg::Context, style: &Style, rect: Rect) {
+    let Rect(Point(left, top), Point(width, height)) = rect;
+
+    nvg.begin_path();
+    nvg.rect(left, top, width, height);
+    nvg.stroke_width(style.line_size / 2.);
+    nvg.stroke_color(style.hover_color);
+    nvg.stroke();
+}
+
+// Pixel inspector
+
+/// Draws a swatch of `rgba` and its components next to the cursor at `point`.
+pub fn draw_pixel_info(nvg: &nanovg::Context, style: &Style, point: Point, rgba: [u8; 4]) {
+    let padding = style.font_size / 4.;
+    let text = format!("R {} G {} B {}", rgba[0], rgba[1], rgba[2]);
+
+    let Point(text_w, text_h) = nvg.text_size(style, &text);
+    let swatch = text_h;
+    let Point(w, h) = Point(swatch + padding + text_w, text_h) + Point(padding, padding) * 2.;
+
+    // Placed like a tooltip, below and to the right of the cursor.
+    let Point(x, y) = (point + Point(style.font_size / 2., style.font_size)).round();
+    nvg.begin_path();
+    nvg.rounded_rect(x, y, w, h, style.frame_corner_size / 2.);
+    nvg.fill_color(style.background_color);
+    nvg.fill();
+    nvg.stroke_width(style.line_size / 2.);
+    nvg.stroke_color(style.passive_color);
+    nvg.stroke();
+
+    nvg.begin_path();
+    nvg.rect(x + padding, y + padding, swatch, swatch);
+    nvg.fill_color(nanovg::Color::rgb_f(rgba[0] as f32 / 255., rgba[1] as f32 / 255.,
+                                        rgba[2] as f32 / 255.));
+    nvg.fill();
+
+    nvg.font_face(&style.font_face);
+    nvg.font_size(style.font_size);
+    nvg.fill_color(style.active_color);
+    nvg.text_align(nanovg::LEFT | nanovg::TOP);
+    nvg.text(x + padding * 2. + swatch, y + padding, &text);
+}
+
+// Measurement
+
+/// Draws a line from `start` to `end`, labelled with `text` at its middle.
+pub fn draw_measurement(nvg: &nanovg::Context, style: &Style, start: Point, end: Point,
+                        text: &str) {
+    nvg.begin_path();
+    nvg.move_to(start.0, start.1);
+    nvg.line_to(end.0, end.1);
+    nvg.circle(start.0, start.1, style.line_size);
+    nvg.circle(end.0, end.1, style.line_size);
+    nvg.stroke_width(style.line_size / 2.);
+    nvg.stroke_color(style.hover_color);
+    nvg.stroke();
+
+    let padding = style.font_size / 4.;
+    let Point(w, h) = nvg.text_size(style, text) + Point(padding, padding) * 2.;
+    let Point(x, y) = (start.lerp(end, 0.5) - Point(w, h) / 2.).round();
+    nvg.begin_path();
+    nvg.rounded_rect(x, y, w, h, style.frame_corner_size / 2.);
+    nvg.fill_color(style.background_color);
+    nvg.fill();
+
+    nvg.font_face(&style.font_face);
+    nvg.font_size(style.font_size);
+    nvg.fill_color(style.active_color);
+    nvg.text_align(nanovg::LEFT | nanovg::TOP);
+    nvg.text(x + padding, y + padding, text);
+}
+
+// Inset
+
+/// Width of the edge by which the picture in picture inset is moved, and the size of
+/// the grip in its corner by which it is resized.
+pub fn inset_border_size(style: &Style) -> f32 { style.font_size / 2. }
+
+pub fn draw_inset_border(nvg: &nanovg::Context, style: &Style, rect: Rect) {
+    let Rect(Point(left, top), Point(width, height)) = rect;
+
+    nvg.begin_path();
+    nvg.rect(left, top, width, height);
+    nvg.stroke_width(inset_border_size(style));
+    nvg.stroke_color(style.background_color);
+    nvg.stroke();
+
+    let grip = inset_border_size(style);
+    let (grip_x, grip_y) = (left + width - grip, top + height - grip);
+    nvg.begin_path();
+    for step in 1..4 {
+        let inset = grip * step as f32 / 4.;
+        nvg.move_to(grip_x + inset, grip_y + grip);
+        nvg.line_to(grip_x + grip, grip_y + inset);
     }
+    nvg.stroke_width(style.line_size / 4.);
+    nvg.stroke_color(style.passive_color);
+    nvg.stroke();
 }
 
 // Label
 
-pub struct Label<'nvg> {
-    nvg: &'nvg nanovg::Context,
+pub struct Label {
     state: RefCell<LabelState>,
     text: Rc<Property<String>>,
+    wrap: Cell<Option<f32>>,
 }
 
 struct LabelState {
     size: Point,
 }
 
-impl<'nvg> Label<'nvg> {
-    pub fn new(nvg: &'nvg nanovg::Context) -> Label<'nvg> {
+impl Label {
+    pub fn new() -> Label {
         Label {
-            nvg: nvg,
             state: RefCell::new(LabelState {
                 size: Point(0., 0.),
             }),
             text: Property::new(String::from("")),
+            wrap: Cell::new(None),
         }
     }
 
     pub fn text(&self) -> Rc<Property<String>> { self.text.clone() }
+
+    /// Breaks the text into lines no wider than `width`, or keeps it on one line if `None`.
+    /// Words that are wider than `width` by themselves are broken between characters.
+    pub fn set_wrap(&self, width: Option<f32>) { self.wrap.set(width) }
 }
 
-impl<'nvg> Widget for Label<'nvg> {
+impl Widget for Label {
     fn size(&self) -> Point { self.state.borrow().size }
-    fn set_size(&self, size: Point) { self.state.borrow_mut().size = size }
-
-    fn size_request(&self) -> Point {
-        self.nvg.font_face(&Style::get().font_face);
-        self.nvg.font_size(Style::get().font_size);
-
-        let mut bounds = [0.; 4];
-        self.nvg.text_bounds(0., 0., &self.text.get(), &mut bounds);
+    fn set_size(&self, size: Point, _style: &Style, _measurer: &TextMeasurer) {
+        self.state.borrow_mut().size = size
+    }
 
-        Point(bounds[2] - bounds[0], bounds[3] - bounds[1])
+    fn size_request(&self, style: &Style, measurer: &TextMeasurer) -> Point {
+        match self.wrap.get() {
+            Some(width) => measurer.text_box_size(style, width, &self.text.get()),
+            None => measurer.text_size(style, &self.text.get())
+        }
     }
 
-    fn render(&self) {
-        self.nvg.font_face(&Style::get().font_face);
-        self.nvg.font_size(Style::get().font_size);
-        self.nvg.fill_color(Style::get().active_color);
-        self.nvg.text_align(nanovg::LEFT | nanovg::TOP);
-        self.nvg.text(0., 0., &self.text.get());
+    fn render(&self, nvg: &nanovg::Context, style: &Style) {
+        nvg.font_face(&style.font_face);
+        nvg.font_size(style.font_size);
+        nvg.fill_color(style.active_color);
+        nvg.text_align(nanovg::LEFT | nanovg::TOP);
+        match self.wrap.get() {
+            Some(width) => nvg.text_box(0., 0., width, &self.text.get()),
+            None => { nvg.text(0., 0., &self.text.get()); }
+        }
     }
 }
 
 // Slider
 
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum SliderScale {
+    Linear,
+    Logarithmic,
+}
+
 #[derive(Copy, Clone, Debug)]
 pub struct SliderPosition {
     pub current: f32,
@@ -171,17 +432,36 @@ impl SliderPosition {
         self.maximum - self.minimum
     }
 
-    pub fn normalized(&self) -> f32 {
-        (self.current - self.minimum) / (self.maximum - self.minimum)
+    // The logarithm is not defined at zero, so a logarithmic scale starts at `step`
+    // if the minimum is not positive.
+    fn log_minimum(&self) -> f32 {
+        self.minimum.max(self.step)
     }
 
-    pub fn denormalized(&self, norm_value: f32) -> SliderPosition {
-        SliderPosition {
-            current: self.minimum + norm_value * (self.maximum - self.minimum),
-            ..*self
+    pub fn normalized(&self, scale: SliderScale) -> f32 {
+        match scale {
+            SliderScale::Linear =>
+                (self.current - self.minimum) / (self.maximum - self.minimum),
+            SliderScale::Logarithmic => {
+                let minimum = self.log_minimum();
+                if self.maximum <= minimum { return 0. }
+                (self.current.max(minimum) / minimum).ln() / (self.maximum / minimum).ln()
+            }
         }
     }
 
+    pub fn denormalized(&self, scale: SliderScale, norm_value: f32) -> SliderPosition {
+        let current = match scale {
+            SliderScale::Linear =>
+                self.minimum + norm_value * (self.maximum - self.minimum),
+            SliderScale::Logarithmic => {
+                let minimum = self.log_minimum();
+                minimum * (self.maximum / minimum).max(1.).powf(norm_value)
+            }
+        };
+        SliderPosition { current: current, ..*self }
+    }
+
     pub fn change(&self, new_value: f32) -> SliderPosition {
         SliderPosition { current: new_value, ..*self }
     }
@@ -191,351 +471,2735 @@ impl SliderPosition {
     }
 }
 
-pub struct Slider<'nvg> {
-    nvg: &'nvg nanovg::Context,
+pub struct Slider {
     state: RefCell<SliderState>,
     position: Rc<Property<SliderPosition>>,
     current_position: Rc<Property<f32>>,
+    default: Cell<f32>,
+    ticks: RefCell<Vec<f32>>,
+    snap: Cell<bool>,
+    animated: Cell<bool>,
+    direction: Direction,
+    scale: SliderScale,
+    unit: Option<String>,
+    enabled: Rc<Property<bool>>,
+    tooltip: Option<String>,
 }
 
 struct SliderState {
     size: Point,
+    // Distance from the ends of the track to the edges, as of the last layout.
+    offset: f32,
     ui_state: State,
+    focused: bool,
+    time: f64,
+    // Normalized value the puck is heading to; `None` until the first frame.
+    target: Option<f32>,
+    // Where the puck was, and when, as it started moving to the target.
+    animation: Option<(f32, f64)>,
+}
+
+impl SliderState {
+    // Normalized value where the puck is drawn.
+    fn puck(&self, target: f32) -> f32 {
+        match self.animation {
+            Some((from, started)) if self.time - started < Slider::animation_time() => {
+                let progress = ((self.time - started) / Slider::animation_time()) as f32;
+                from + (target - from) * ease_out(progress)
+            }
+            _ => target
+        }
+    }
 }
 
-impl<'nvg> Slider<'nvg> {
-    pub fn new(nvg: &'nvg nanovg::Context, position: SliderPosition) -> Slider<'nvg> {
+impl Slider {
+    pub fn new(position: SliderPosition) -> Slider {
         let position = Property::with_validator(position, SliderPosition::validator);
         let current_position = Property::derived(position.clone(),
             |position, current| SliderPosition { current: current, ..*position },
             |position|        position.current);
         Slider {
-            nvg: nvg,
             state: RefCell::new(SliderState {
                 size: Point(0., 0.),
+                offset: 0.,
                 ui_state: State::Passive,
+                focused: false,
+                time: 0.,
+                target: None,
+                animation: None,
             }),
+            default: Cell::new(position.get().current),
+            ticks: RefCell::new(Vec::new()),
+            snap: Cell::new(false),
+            animated: Cell::new(false),
             position: position,
             current_position: current_position,
+            direction: Direction::Horizontal,
+            scale: SliderScale::Linear,
+            unit: None,
+            enabled: Property::new(true),
+            tooltip: None,
         }
     }
 
+    /// Creates a slider that has its minimum at the bottom and its maximum at the top.
+    pub fn new_vertical(position: SliderPosition) -> Slider {
+        Slider { direction: Direction::Vertical, ..Slider::new(position) }
+    }
+
+    pub fn with_tooltip(self, tooltip: String) -> Slider {
+        Slider { tooltip: Some(tooltip), ..self }
+    }
+
+    /// Shows the value with `unit` after it next to the puck while it is being dragged.
+    pub fn with_unit(self, unit: String) -> Slider {
+        Slider { unit: Some(unit), ..self }
+    }
+
+    /// Maps the position of the puck to the value logarithmically or linearly.
+    pub fn with_scale(self, scale: SliderScale) -> Slider {
+        Slider { scale: scale, ..self }
+    }
+
     pub fn position(&self) -> Rc<Property<SliderPosition>> { self.position.clone() }
     pub fn current_position(&self) -> Rc<Property<f32>> { self.current_position.clone() }
+    pub fn enabled(&self) -> Rc<Property<bool>> { self.enabled.clone() }
+
+    pub fn set_default(&self, value: f32) { self.default.set(value) }
+
+    /// Whether the slider is hovered or being dragged.
+    pub fn ui_state(&self) -> State { self.state.borrow().ui_state }
+
+    /// Marks the given values along the track.
+    pub fn set_ticks(&self, ticks: Vec<f32>) { *self.ticks.borrow_mut() = ticks }
+
+    /// Makes the puck jump to the nearest tick when it is released.
+    pub fn set_snap(&self, snap: bool) { self.snap.set(snap) }
+
+    /// Makes the puck glide to values set from elsewhere, e.g. by a preset, rather than
+    /// jump there; it still follows the mouse directly while dragged.
+    pub fn set_animated(&self, animated: bool) { self.animated.set(animated) }
+
+    // The current value, with as many decimals as the step has.
+    fn formatted_value(&self) -> String {
+        let pos = self.position.get();
+        let precision = (-pos.step.log10()).ceil().max(0.) as usize;
+        format!("{:.*}", precision, pos.current)
+    }
+
+    fn value_text(&self) -> Option<String> {
+        self.unit.as_ref().map(|unit| {
+            if unit.is_empty() {
+                self.formatted_value()
+            } else {
+                format!("{} {}", self.formatted_value(), unit)
+            }
+        })
+    }
+
+    fn nearest_tick(&self, value: f32) -> Option<f32> {
+        self.ticks.borrow().iter().cloned().fold(None, |nearest, tick| match nearest {
+            Some(nearest) if (nearest - value).abs() <= (tick - value).abs() => Some(nearest),
+            _ => Some(tick)
+        })
+    }
+
+    fn slider_offset(style: &Style) -> f32 { style.font_size / 2. }
+    fn puck_radius(style: &Style) -> f32 { Slider::slider_offset(style) / 2. }
+    fn animation_time() -> f64 { 0.2 } // seconds
 
-    fn slider_offset() -> f32 { Style::get().font_size / 2. }
-    fn puck_radius() -> f32 { Slider::slider_offset() / 2. }
+    // Moves the value by `steps` steps; on a logarithmic scale, a step is 1% of the track,
+    // but never less than the step of the position.
+    fn offset(&self, steps: f32) -> SliderPosition {
+        let pos = self.position.get();
+        match self.scale {
+            SliderScale::Linear => pos.offset(pos.step * steps),
+            SliderScale::Logarithmic => {
+                let norm_value = pos.normalized(self.scale) + steps / 100.;
+                let current = pos.denormalized(self.scale, norm_value).current;
+                if steps > 0. {
+                    pos.change(current.max(pos.current + pos.step * steps))
+                } else {
+                    pos.change(current.min(pos.current + pos.step * steps))
+                }
+            }
+        }
+    }
+
+    fn oriented(&self, point: Point) -> Point {
+        match self.direction {
+            Direction::Horizontal => point,
+            Direction::Vertical   => Point(point.1, point.0)
+        }
+    }
+
+    // Ends of the track, at the minimum and at the maximum.
+    fn track(&self) -> (Point, Point) {
+        let state = self.state.borrow();
+        let (Point(width, height), offset) = (state.size, state.offset);
+        match self.direction {
+            Direction::Horizontal =>
+                (Point(offset, height / 2.), Point(width - offset, height / 2.)),
+            Direction::Vertical =>
+                (Point(width / 2., height - offset), Point(width / 2., offset))
+        }
+    }
 }
 
-impl<'nvg> Widget for Slider<'nvg> {
+impl Widget for Slider {
     fn size(&self) -> Point { self.state.borrow().size }
-    fn set_size(&self, size: Point) { self.state.borrow_mut().size = size }
+    fn set_size(&self, size: Point, style: &Style, _measurer: &TextMeasurer) {
+        let mut state = self.state.borrow_mut();
+        state.size = size;
+        state.offset = Slider::slider_offset(style);
+    }
+
+    fn size_request(&self, style: &Style, _measurer: &TextMeasurer) -> Point {
+        self.oriented(Point(Slider::slider_offset(style) * 20.,
+                            Slider::slider_offset(style) * 2. + style.line_size))
+    }
 
-    fn size_request(&self) -> Point {
-        Point(Slider::slider_offset() * 20.,
-              Slider::slider_offset() * 2. + Style::get().line_size)
+    fn min_size(&self, style: &Style) -> Option<Point> {
+        // Shorter sliders can't be positioned with any precision.
+        Some(self.oriented(Point(style.font_size * 5., 0.)))
+    }
+
+    fn prepare(&self, time: f64) {
+        let target = self.position.get().normalized(self.scale);
+        let mut state = self.state.borrow_mut();
+        if let Some((_, started)) = state.animation {
+            // Kept for a frame after it ends, so that the puck gets drawn at the target.
+            if state.time - started >= Slider::animation_time() { state.animation = None }
+        }
+        state.time = time;
+        match state.target {
+            Some(old_target) if old_target != target => {
+                if self.animated.get() && state.ui_state != State::Active {
+                    state.animation = Some((state.puck(old_target), time))
+                } else {
+                    state.animation = None
+                }
+            }
+            _ => ()
+        }
+        state.target = Some(target)
     }
 
-    fn render(&self) {
+    fn animating(&self) -> bool { self.state.borrow().animation.is_some() }
+
+    fn render(&self, nvg: &nanovg::Context, style: &Style) {
         let state = self.state.borrow();
 
-        let mid_y = self.size().1 / 2.;
-        let (left_x, right_x) = (Slider::slider_offset(), self.size().0 - Slider::slider_offset());
-        let puck_x = left_x + (right_x - left_x) * self.position.get().normalized();
+        let (start, end) = self.track();
+        let target = self.position.get().normalized(self.scale);
+        let Point(puck_x, puck_y) = start.lerp(end, state.puck(target));
+
+        if state.focused {
+            let inset = style.line_size / 4.;
+            nvg.stroke_width(style.line_size / 2.);
+            nvg.stroke_color(style.hover_color);
+            nvg.begin_path();
+            nvg.rounded_rect(inset, inset, state.size.0 - inset * 2., state.size.1 - inset * 2.,
+                             style.frame_corner_size / 2.);
+            nvg.stroke();
+        }
 
-        self.nvg.stroke_width(Style::get().line_size);
+        nvg.stroke_width(style.line_size);
 
-        self.nvg.stroke_color(match state.ui_state {
-            State::Passive | State::Hovered => Style::get().active_color,
-            State::Active => Style::get().hover_color
+        nvg.stroke_color(match state.ui_state {
+            _ if !self.enabled.get() => style.disabled_color,
+            State::Passive | State::Hovered => style.active_color,
+            State::Active => style.hover_color
         });
-        self.nvg.begin_path();
-        self.nvg.move_to(left_x, mid_y);
-        self.nvg.line_to(right_x, mid_y);
-        self.nvg.stroke();
-
-        self.nvg.fill_color(match state.ui_state {
-            State::Passive => Style::get().active_color,
-            State::Hovered | State::Active => Style::get().hover_color
+        nvg.begin_path();
+        nvg.move_to(start.0, start.1);
+        nvg.line_to(end.0, end.1);
+        nvg.stroke();
+
+        let position = self.position.get();
+        let across = self.oriented(Point(0., Slider::puck_radius(style)));
+        nvg.stroke_width(style.line_size / 2.);
+        nvg.stroke_color(style.passive_color);
+        nvg.begin_path();
+        for &tick in self.ticks.borrow().iter() {
+            let Point(x, y) = start.lerp(end, position.change(tick).normalized(self.scale));
+            nvg.move_to(x - across.0, y - across.1);
+            nvg.line_to(x + across.0, y + across.1);
+        }
+        nvg.stroke();
+
+        nvg.fill_color(match state.ui_state {
+            _ if !self.enabled.get() => style.disabled_color,
+            State::Passive => style.active_color,
+            State::Hovered | State::Active => style.hover_color
         });
-        self.nvg.begin_path();
-        self.nvg.circle(puck_x, mid_y, Slider::puck_radius());
-        self.nvg.fill();
+        nvg.begin_path();
+        nvg.circle(puck_x, puck_y, Slider::puck_radius(style));
+        nvg.fill();
+
+        if let (State::Active, Some(text)) = (state.ui_state, self.value_text()) {
+            let padding = style.font_size / 4.;
+            let Point(w, h) = nvg.text_size(style, &text) + Point(padding, padding) * 2.;
+
+            // Centered above the puck; it does not fit into the slider, so it is drawn
+            // over whatever is above it.
+            let puck_top = puck_y - Slider::puck_radius(style);
+            let Point(x, y) = Point(puck_x - w / 2., puck_top - padding - h).round();
+            nvg.save();
+            nvg.reset_scissor();
+            nvg.begin_path();
+            nvg.rounded_rect(x, y, w, h, style.frame_corner_size / 2.);
+            nvg.fill_color(style.background_color);
+            nvg.fill();
+            nvg.stroke_width(style.line_size / 2.);
+            nvg.stroke_color(style.hover_color);
+            nvg.stroke();
+
+            nvg.fill_color(style.active_color);
+            nvg.text_align(nanovg::LEFT | nanovg::TOP);
+            nvg.text(x + padding, y + padding, &text);
+            nvg.restore();
+        }
     }
 
     fn project(&self, point: Point) -> Option<(&Widget, Point)> {
         Some((self, point))
     }
 
+    fn tooltip(&self) -> Option<String> { self.tooltip.clone() }
+
+    fn is_enabled(&self) -> bool { self.enabled.get() }
+    fn set_enabled(&self, enabled: bool) { self.enabled.set(enabled) }
+
+    fn double_click(&self, _point: Point) {
+        if !self.enabled.get() { return }
+        let pos = self.position.get();
+        self.position.set(pos.change(self.default.get()))
+    }
+
+    fn context_menu(&self) -> Vec<String> {
+        vec!["Reset".to_string(), "Minimum".to_string(), "Maximum".to_string()]
+    }
+
+    fn context_action(&self, index: usize) {
+        if !self.enabled.get() { return }
+        let pos = self.position.get();
+        match index {
+            0 => self.position.set(pos.change(self.default.get())),
+            1 => self.position.set(pos.change(pos.minimum)),
+            2 => self.position.set(pos.change(pos.maximum)),
+            _ => ()
+        }
+    }
+
+    fn copy_text(&self) -> Option<String> { Some(self.formatted_value()) }
+
     fn mouse_in(&self) {
         self.state.borrow_mut().ui_state = State::Hovered
     }
 
     fn mouse_down(&self, point: Point) {
+        // A slider that is not active does not follow the mouse.
+        if !self.enabled.get() { return }
         self.state.borrow_mut().ui_state = State::Active;
         self.mouse_move(point);
     }
 
     fn mouse_move(&self, point: Point) {
-        let (left_x, right_x) = (Slider::slider_offset(), self.size().0 - Slider::slider_offset());
-        let norm_value = (point.0 - left_x) / (right_x - left_x);
+        let (start, end) = self.track();
+        let (Point(start, _), Point(end, _), Point(point, _)) =
+            (self.oriented(start), self.oriented(end), self.oriented(point));
+        let norm_value = (point - start) / (end - start);
         if self.state.borrow().ui_state == State::Active {
-            self.position.set(self.position.get().denormalized(norm_value))
+            self.position.set(self.position.get().denormalized(self.scale, norm_value))
         }
     }
 
     fn mouse_scroll(&self, offset: Point) {
-        let pos = self.position.get();
+        if !self.enabled.get() { return }
         if offset.1 > 0. {
-            self.position.set(pos.offset(pos.step))
+            self.position.set(self.offset(1.))
         } else if offset.1 < 0. {
-            self.position.set(pos.offset(-pos.step))
+            self.position.set(self.offset(-1.))
         }
     }
 
     fn mouse_up(&self, _point: Point) {
-        self.state.borrow_mut().ui_state = State::Hovered
+        let dragging = self.state.borrow().ui_state == State::Active;
+        self.state.borrow_mut().ui_state = State::Hovered;
+
+        if dragging && self.snap.get() {
+            let pos = self.position.get();
+            if let Some(tick) = self.nearest_tick(pos.current) {
+                self.position.set(pos.change(tick))
+            }
+        }
     }
 
     fn mouse_out(&self) {
         self.state.borrow_mut().ui_state = State::Passive
     }
+
+    fn accepts_focus(&self) -> bool { self.enabled.get() }
+
+    fn focus_in(&self) {
+        self.state.borrow_mut().focused = true
+    }
+
+    fn focus_out(&self) {
+        self.state.borrow_mut().focused = false
+    }
+
+    fn key_input(&self, key: glfw::Key, _modifiers: glfw::Modifiers) {
+        if !self.enabled.get() { return }
+        let pos = self.position.get();
+        match key {
+            glfw::Key::Left | glfw::Key::Down =>
+                self.position.set(self.offset(-1.)),
+            glfw::Key::Right | glfw::Key::Up =>
+                self.position.set(self.offset(1.)),
+            glfw::Key::PageDown =>
+                self.position.set(self.offset(-10.)),
+            glfw::Key::PageUp =>
+                self.position.set(self.offset(10.)),
+            glfw::Key::Home =>
+                self.position.set(pos.change(pos.minimum)),
+            glfw::Key::End =>
+                self.position.set(pos.change(pos.maximum)),
+            _ => ()
+        }
+    }
 }
 
-// BoxLayout
+// Button
 
-pub struct BoxLayout<'nvg> {
-    nvg: &'nvg nanovg::Context,
-    direction: Direction,
-    children: Vec<Box<Widget + 'nvg>>,
-    state: RefCell<BoxLayoutState>,
+pub struct Button<'nvg> {
+    state: RefCell<ButtonState>,
+    text: Rc<Property<String>>,
+    handlers: RefCell<Vec<Box<Fn() + 'nvg>>>,
+    enabled: Rc<Property<bool>>,
+    tooltip: Option<String>,
 }
 
-struct BoxLayoutState {
+struct ButtonState {
     size: Point,
+    ui_state: State,
 }
 
-impl<'nvg> BoxLayout<'nvg> {
-    pub fn new(nvg: &'nvg nanovg::Context, dir: Direction) -> BoxLayout<'nvg> {
-        BoxLayout {
-            nvg: nvg,
-            direction: dir,
-            children: Vec::new(),
-            state: RefCell::new(BoxLayoutState {
+impl<'nvg> Button<'nvg> {
+    pub fn new(text: String) -> Button<'nvg> {
+        Button {
+            state: RefCell::new(ButtonState {
                 size: Point(0., 0.),
-            })
+                ui_state: State::Passive,
+            }),
+            text: Property::new(text),
+            handlers: RefCell::new(Vec::new()),
+            enabled: Property::new(true),
+            tooltip: None,
         }
     }
 
-    pub fn horz(nvg: &'nvg nanovg::Context) -> BoxLayout {
-        BoxLayout::new(nvg, Direction::Horizontal)
-    }
-
-    pub fn vert(nvg: &'nvg nanovg::Context) -> BoxLayout {
-        BoxLayout::new(nvg, Direction::Vertical)
+    pub fn with_tooltip(self, tooltip: String) -> Button<'nvg> {
+        Button { tooltip: Some(tooltip), ..self }
     }
-}
 
-impl<'nvg> Widget for BoxLayout<'nvg> {
-    fn size(&self) -> Point { self.state.borrow().size }
+    pub fn text(&self) -> Rc<Property<String>> { self.text.clone() }
 
-    fn set_size(&self, size: Point) {
-        self.state.borrow_mut().size = size;
+    /// A disabled button is drawn grayed out, and can't be clicked.
+    pub fn enabled(&self) -> Rc<Property<bool>> { self.enabled.clone() }
 
-        let request = self.size_request();
-        for child in &self.children {
-            match self.direction {
-                Direction::Horizontal => {
-                    let child_width = child.size_request().0 * size.0 / request.0;
-                    child.set_size(Point(child_width, size.1));
-                },
-                Direction::Vertical => {
-                    let child_height = child.size_request().1 * size.1 / request.1;
-                    child.set_size(Point(size.0, child_height));
-                }
-            }
-        }
+    pub fn on_click<F>(&self, handler: F) where F: Fn() + 'nvg {
+        self.handlers.borrow_mut().push(Box::new(handler))
     }
 
-    fn size_request(&self) -> Point {
-        let requests = self.children.iter().
-            map(|child| { child.size_request() }).collect::<Vec<_>>();
-        let xs = requests.iter().map(|req| { req.0 });
-        let ys = requests.iter().map(|req| { req.1 });
-
-        match self.direction {
-            Direction::Horizontal =>
-                Point(xs.sum(), ys.fold(0., |l, r| { l.max(r) })),
-            Direction::Vertical =>
-                Point(xs.fold(0., |l, r| { l.max(r) }), ys.sum())
-        }
+    fn padding(style: &Style) -> Point {
+        Point(style.font_size / 2., style.font_size / 4.)
     }
+}
 
-    fn prepare(&self) {
-        for child in &self.children { child.prepare() }
+impl<'nvg> Widget for Button<'nvg> {
+    fn size(&self) -> Point { self.state.borrow().size }
+    fn set_size(&self, size: Point, _style: &Style, _measurer: &TextMeasurer) {
+        self.state.borrow_mut().size = size
     }
 
-    fn need_reflow(&self) -> bool {
-        self.children.iter().fold(false, |acc, child| { acc || child.need_reflow() })
+    fn size_request(&self, style: &Style, measurer: &TextMeasurer) -> Point {
+        measurer.text_size(style, &self.text.get()) + Button::padding(style) * 2.
     }
 
-    fn render(&self) {
-        let (mut x, mut y) = (0., 0.);
-        for child in &self.children {
-            let Point(w, h) = child.size();
-
-            self.nvg.save();
-            self.nvg.translate(x, y);
-            self.nvg.scissor(0., 0., w, h);
-            child.render();
-            self.nvg.restore();
+    fn render(&self, nvg: &nanovg::Context, style: &Style) {
+        let state = self.state.borrow();
 
-            match self.direction {
-                Direction::Horizontal => x += w,
-                Direction::Vertical   => y += h
+        let inset = style.line_size / 2.;
+        let Point(w, h) = state.size;
+        nvg.begin_path();
+        nvg.rounded_rect(inset, inset, w - inset * 2., h - inset * 2.,
+                         style.frame_corner_size / 2.);
+        nvg.stroke_width(style.line_size);
+        match state.ui_state {
+            _ if !self.enabled.get() => {
+                nvg.stroke_color(style.disabled_color);
+                nvg.stroke();
+                nvg.fill_color(style.disabled_color);
+            },
+            State::Passive => {
+                nvg.stroke_color(style.active_color);
+                nvg.stroke();
+                nvg.fill_color(style.active_color);
+            },
+            State::Hovered => {
+                nvg.stroke_color(style.hover_color);
+                nvg.stroke();
+                nvg.fill_color(style.hover_color);
+            },
+            State::Active => {
+                nvg.fill_color(style.hover_color);
+                nvg.fill();
+                nvg.fill_color(style.background_color);
             }
         }
+
+        nvg.font_face(&style.font_face);
+        nvg.font_size(style.font_size);
+        nvg.text_align(nanovg::CENTER | nanovg::MIDDLE);
+        nvg.text(w / 2., h / 2., &self.text.get());
     }
 
     fn project(&self, point: Point) -> Option<(&Widget, Point)> {
-        let mut origin = Point(0., 0.);
-        for child in &self.children {
-            let size = child.size();
+        Some((self, point))
+    }
 
-            if Rect(origin, size).contains(point) {
-                return child.project(point - origin)
-            }
+    fn tooltip(&self) -> Option<String> { self.tooltip.clone() }
 
-            match self.direction {
-                Direction::Horizontal => origin.0 += size.0,
-                Direction::Vertical   => origin.1 += size.1
-            }
-        }
+    fn is_enabled(&self) -> bool { self.enabled.get() }
+    fn set_enabled(&self, enabled: bool) { self.enabled.set(enabled) }
 
-        None
+    fn mouse_in(&self) {
+        self.state.borrow_mut().ui_state = State::Hovered
     }
-}
 
-impl<'nvg> Container<'nvg> for BoxLayout<'nvg> {
-    fn add(&mut self, widget: Box<Widget + 'nvg>) {
-        self.children.push(widget)
+    fn mouse_down(&self, _point: Point) {
+        self.state.borrow_mut().ui_state = State::Active
     }
 
-    fn remove(&mut self, widget: &Widget) -> Box<Widget + 'nvg> {
-        let index = self.iter().position(|elem| { elem.is(widget) });
-        self.children.remove(index.unwrap())
+    // Unlike a click, this fires however far the mouse moved, as long as it is
+    // released over the button; releasing it elsewhere cancels the press.
+    fn mouse_up(&self, point: Point) {
+        let inside = self.size().as_rect().contains(point);
+        self.state.borrow_mut().ui_state = if inside { State::Hovered } else { State::Passive };
+        if !inside || !self.enabled.get() { return }
+        for handler in self.handlers.borrow().iter() {
+            handler()
+        }
     }
 
-    fn iter<'a>(&'a self) -> Iter<'a> {
-        Iter { elements: &self.children, index: 0 }
+    fn mouse_out(&self) {
+        self.state.borrow_mut().ui_state = State::Passive
     }
 }
 
-// Frame
+// Checkbox
 
-pub struct Frame<'nvg> {
-    nvg: &'nvg nanovg::Context,
-    widget: Box<Widget + 'nvg>,
-    state: RefCell<FrameState>,
+pub struct Checkbox {
+    state: RefCell<CheckboxState>,
+    value: Rc<Property<bool>>,
+    text: Rc<Property<String>>,
+    enabled: Rc<Property<bool>>,
+    tooltip: Option<String>,
 }
 
-struct FrameState {
+struct CheckboxState {
     size: Point,
-    position: Point,
-    moving: Option<Point>,
+    ui_state: State,
 }
 
-impl<'nvg> Frame<'nvg> {
-    pub fn new(nvg: &'nvg nanovg::Context, widget: Box<Widget + 'nvg>) -> Frame<'nvg> {
-        Frame {
-            nvg: nvg,
-            widget: widget,
-            state: RefCell::new(FrameState {
+impl Checkbox {
+    pub fn new(value: bool) -> Checkbox {
+        Checkbox {
+            state: RefCell::new(CheckboxState {
                 size: Point(0., 0.),
-                position: Point(0., 0.),
-                moving: None,
-            })
+                ui_state: State::Passive,
+            }),
+            value: Property::new(value),
+            text: Property::new(String::from("")),
+            enabled: Property::new(true),
+            tooltip: None,
         }
     }
 
-    pub fn position(&self) -> Point {
-        self.state.borrow().position
+    pub fn with_tooltip(self, tooltip: String) -> Checkbox {
+        Checkbox { tooltip: Some(tooltip), ..self }
     }
 
-    pub fn set_position(&mut self, point: Point) {
-        self.state.borrow_mut().position = point
-    }
+    pub fn value(&self) -> Rc<Property<bool>> { self.value.clone() }
+    pub fn text(&self) -> Rc<Property<String>> { self.text.clone() }
 
-    fn content_offset() -> Point {
-        Point(Style::get().frame_corner_size, Style::get().frame_corner_size)
+    /// A disabled checkbox is drawn grayed out, and can't be toggled.
+    pub fn enabled(&self) -> Rc<Property<bool>> { self.enabled.clone() }
+
+    fn box_size(style: &Style) -> f32 { style.font_size * 0.75 }
+    fn text_offset(style: &Style) -> f32 { Checkbox::box_size(style) + style.font_size / 3. }
+
+    fn text_size(&self, style: &Style, measurer: &TextMeasurer) -> Option<Point> {
+        let text = self.text.get();
+        if text.is_empty() { return None }
+
+        Some(measurer.text_size(style, &text))
     }
 }
 
-impl<'nvg> Widget for Frame<'nvg> {
+impl Widget for Checkbox {
     fn size(&self) -> Point { self.state.borrow().size }
-
-    fn set_size(&self, size: Point) {
-        self.state.borrow_mut().size = size;
-        self.widget.set_size(size - Frame::content_offset() * 2.)
+    fn set_size(&self, size: Point, _style: &Style, _measurer: &TextMeasurer) {
+        self.state.borrow_mut().size = size
     }
 
-    fn size_request(&self) -> Point {
-        self.widget.size_request() + Frame::content_offset() * 2.
+    fn size_request(&self, style: &Style, measurer: &TextMeasurer) -> Point {
+        let box_size = Checkbox::box_size(style) + style.line_size;
+        match self.text_size(style, measurer) {
+            Some(Point(w, h)) => Point(Checkbox::text_offset(style) + w, box_size.max(h)),
+            None => Point(box_size, box_size)
+        }
     }
 
-    fn need_reflow(&self) -> bool { self.widget.need_reflow() }
-
-    fn render(&self) {
+    fn render(&self, nvg: &nanovg::Context, style: &Style) {
+        let state = self.state.borrow();
+
+        let box_size = Checkbox::box_size(style);
+        let (left_x, top_y) = (style.line_size / 2., (state.size.1 - box_size) / 2.);
+
+        nvg.stroke_width(style.line_size);
+        nvg.stroke_color(match state.ui_state {
+            _ if !self.enabled.get() => style.disabled_color,
+            State::Passive | State::Hovered => style.active_color,
+            State::Active => style.hover_color
+        });
+        nvg.begin_path();
+        nvg.rect(left_x, top_y, box_size, box_size);
+        nvg.stroke();
+
+        if self.value.get() {
+            let inset = box_size / 4.;
+            nvg.fill_color(match state.ui_state {
+                _ if !self.enabled.get() => style.disabled_color,
+                State::Passive => style.active_color,
+                State::Hovered | State::Active => style.hover_color
+            });
+            nvg.begin_path();
+            nvg.rect(left_x + inset, top_y + inset,
+                     box_size - inset * 2., box_size - inset * 2.);
+            nvg.fill();
+        }
+
+        nvg.font_face(&style.font_face);
+        nvg.font_size(style.font_size);
+        let text_color =
+            if self.enabled.get() { style.active_color } else { style.disabled_color };
+        nvg.fill_color(text_color);
+        nvg.text_align(nanovg::LEFT | nanovg::MIDDLE);
+        nvg.text(Checkbox::text_offset(style), state.size.1 / 2., &self.text.get());
+    }
+
+    fn project(&self, point: Point) -> Option<(&Widget, Point)> {
+        Some((self, point))
+    }
+
+    fn tooltip(&self) -> Option<String> { self.tooltip.clone() }
+
+    fn mouse_in(&self) {
+        self.state.borrow_mut().ui_state = State::Hovered
+    }
+
+    fn mouse_down(&self, _point: Point) {
+        self.state.borrow_mut().ui_state = State::Active
+    }
+
+    // Toggles on release over the checkbox, for the same reason as `Button::mouse_up`.
+    fn mouse_up(&self, point: Point) {
+        let inside = self.size().as_rect().contains(point);
+        self.state.borrow_mut().ui_state = if inside { State::Hovered } else { State::Passive };
+        if !inside || !self.enabled.get() { return }
+        self.value.write(|value| *value = !*value)
+    }
+
+    fn is_enabled(&self) -> bool { self.enabled.get() }
+    fn set_enabled(&self, enabled: bool) { self.enabled.set(enabled) }
+
+    fn mouse_out(&self) {
+        self.state.borrow_mut().ui_state = State::Passive
+    }
+}
+
+// RadioGroup
+
+pub struct RadioGroup {
+    state: RefCell<RadioGroupState>,
+    options: Vec<String>,
+    direction: Direction,
+    selected: Rc<Property<usize>>,
+    enabled: Rc<Property<bool>>,
+    tooltip: Option<String>,
+}
+
+struct RadioGroupState {
+    size: Point,
+    ui_state: State,
+    hovered_item: Option<usize>,
+    // Computed during layout, so that input can be matched to options without measuring.
+    item_rects: Vec<Rect>,
+}
+
+impl RadioGroup {
+    pub fn new(options: Vec<String>, direction: Direction, selected: usize) -> RadioGroup {
+        RadioGroup {
+            state: RefCell::new(RadioGroupState {
+                size: Point(0., 0.),
+                ui_state: State::Passive,
+                hovered_item: None,
+                item_rects: Vec::new(),
+            }),
+            options: options,
+            direction: direction,
+            selected: Property::new(selected),
+            enabled: Property::new(true),
+            tooltip: None,
+        }
+    }
+
+    pub fn with_tooltip(self, tooltip: String) -> RadioGroup {
+        RadioGroup { tooltip: Some(tooltip), ..self }
+    }
+
+    pub fn selected(&self) -> Rc<Property<usize>> { self.selected.clone() }
+    pub fn enabled(&self) -> Rc<Property<bool>> { self.enabled.clone() }
+
+    fn circle_size(style: &Style) -> f32 { style.font_size * 0.75 }
+    fn text_offset(style: &Style) -> f32 {
+        RadioGroup::circle_size(style) + style.font_size / 3.
+    }
+    fn spacing(style: &Style) -> f32 { style.font_size / 2. }
+
+    // Area taken by every option, i.e. its circle and its label.
+    fn item_rects(&self, style: &Style, measurer: &TextMeasurer) -> Vec<Rect> {
+        let circle_size = RadioGroup::circle_size(style) + style.line_size;
+        let mut offset = 0.;
+        self.options.iter().map(|option| {
+            let Point(text_w, text_h) = measurer.text_size(style, option);
+            let size = Point(RadioGroup::text_offset(style) + text_w, circle_size.max(text_h));
+
+            let (origin, extent) = match self.direction {
+                Direction::Horizontal => (Point(offset, 0.), size.0),
+                Direction::Vertical   => (Point(0., offset), size.1)
+            };
+            offset += extent + RadioGroup::spacing(style);
+            Rect(origin, size)
+        }).collect()
+    }
+
+    fn item_at(&self, point: Point) -> Option<usize> {
+        self.state.borrow().item_rects.iter().position(|rect| rect.contains(point))
+    }
+}
+
+impl Widget for RadioGroup {
+    fn size(&self) -> Point { self.state.borrow().size }
+
+    fn set_size(&self, size: Point, style: &Style, measurer: &TextMeasurer) {
+        let item_rects = self.item_rects(style, measurer);
+        let mut state = self.state.borrow_mut();
+        state.size = size;
+        state.item_rects = item_rects
+    }
+
+    fn size_request(&self, style: &Style, measurer: &TextMeasurer) -> Point {
+        self.item_rects(style, measurer).into_iter()
+            .fold(Rect(Point(0., 0.), Point(0., 0.)), Rect::union)
+            .size()
+    }
+
+    fn render(&self, nvg: &nanovg::Context, style: &Style) {
+        let state = self.state.borrow();
+        let selected = self.selected.get();
+        let enabled = self.enabled.get();
+
+        let radius = RadioGroup::circle_size(style) / 2.;
+        nvg.stroke_width(style.line_size);
+        nvg.font_face(&style.font_face);
+        nvg.font_size(style.font_size);
+        nvg.text_align(nanovg::LEFT | nanovg::MIDDLE);
+        for (index, (option, rect)) in self.options.iter().zip(&state.item_rects).enumerate() {
+            let Rect(Point(left, top), Point(_, height)) = *rect;
+            let ui_state = if state.hovered_item == Some(index) {
+                state.ui_state
+            } else {
+                State::Passive
+            };
+            let (center_x, center_y) = (left + style.line_size / 2. + radius, top + height / 2.);
+
+            nvg.stroke_color(match ui_state {
+                _ if !enabled => style.disabled_color,
+                State::Passive | State::Hovered => style.active_color,
+                State::Active => style.hover_color
+            });
+            nvg.begin_path();
+            nvg.circle(center_x, center_y, radius);
+            nvg.stroke();
+
+            if index == selected {
+                nvg.fill_color(match ui_state {
+                    _ if !enabled => style.disabled_color,
+                    State::Passive => style.active_color,
+                    State::Hovered | State::Active => style.hover_color
+                });
+                nvg.begin_path();
+                nvg.circle(center_x, center_y, radius / 2.);
+                nvg.fill();
+            }
+
+            nvg.fill_color(if enabled { style.active_color } else { style.disabled_color });
+            nvg.text(left + RadioGroup::text_offset(style), center_y, option);
+        }
+    }
+
+    fn project(&self, point: Point) -> Option<(&Widget, Point)> {
+        // Only the options themselves are clickable, not the spacing between them.
+        self.item_at(point).map(|_| (self as &Widget, point))
+    }
+
+    fn tooltip(&self) -> Option<String> { self.tooltip.clone() }
+
+    fn mouse_in(&self) {
+        self.state.borrow_mut().ui_state = State::Hovered
+    }
+
+    fn mouse_move(&self, point: Point) {
+        let hovered_item = self.item_at(point);
+        self.state.borrow_mut().hovered_item = hovered_item
+    }
+
+    fn mouse_down(&self, _point: Point) {
+        self.state.borrow_mut().ui_state = State::Active
+    }
+
+    fn mouse_up(&self, _point: Point) {
+        self.state.borrow_mut().ui_state = State::Hovered
+    }
+
+    fn is_enabled(&self) -> bool { self.enabled.get() }
+    fn set_enabled(&self, enabled: bool) { self.enabled.set(enabled) }
+
+    fn click(&self, point: Point) {
+        if !self.enabled.get() { return }
+        if let Some(index) = self.item_at(point) {
+            if index != self.selected.get() {
+                self.selected.set(index)
+            }
+        }
+    }
+
+    fn mouse_out(&self) {
+        let mut state = self.state.borrow_mut();
+        state.ui_state = State::Passive;
+        state.hovered_item = None
+    }
+}
+
+// Dropdown
+
+pub struct Dropdown {
+    state: RefCell<DropdownState>,
+    options: Rc<Property<Vec<String>>>,
+    selected: Rc<Property<usize>>,
+    enabled: Rc<Property<bool>>,
+    tooltip: Option<String>,
+}
+
+struct DropdownState {
+    size: Point,
+    ui_state: State,
+    expanded: bool,
+    hovered_item: Option<usize>,
+    reflow: bool,
+    // Row height as of the last layout, for `item_at`.
+    row_height: f32,
+}
+
+impl Dropdown {
+    pub fn new(options: Vec<String>, selected: usize) -> Dropdown {
+        Dropdown {
+            state: RefCell::new(DropdownState {
+                size: Point(0., 0.),
+                ui_state: State::Passive,
+                expanded: false,
+                hovered_item: None,
+                reflow: false,
+                row_height: 0.,
+            }),
+            options: Property::new(options),
+            selected: Property::new(selected),
+            enabled: Property::new(true),
+            tooltip: None,
+        }
+    }
+
+    pub fn with_tooltip(self, tooltip: String) -> Dropdown {
+        Dropdown { tooltip: Some(tooltip), ..self }
+    }
+
+    pub fn options(&self) -> Rc<Property<Vec<String>>> { self.options.clone() }
+    pub fn selected(&self) -> Rc<Property<usize>> { self.selected.clone() }
+    pub fn enabled(&self) -> Rc<Property<bool>> { self.enabled.clone() }
+
+    fn padding(style: &Style) -> f32 { style.font_size / 4. }
+    fn row_height(style: &Style) -> f32 { style.font_size + Dropdown::padding(style) * 2. }
+    fn arrow_size(style: &Style) -> f32 { style.font_size / 2. }
+
+    fn set_expanded(&self, expanded: bool) {
+        let mut state = self.state.borrow_mut();
+        if state.expanded != expanded {
+            state.expanded = expanded;
+            state.hovered_item = None;
+            state.reflow = true;
+        }
+    }
+
+    fn item_at(&self, point: Point) -> Option<usize> {
+        let row_height = self.state.borrow().row_height;
+        let row = ((point.1 - row_height) / row_height).floor();
+        if row >= 0. && (row as usize) < self.options.read(|options| options.len()) {
+            Some(row as usize)
+        } else {
+            None
+        }
+    }
+}
+
+impl Widget for Dropdown {
+    fn size(&self) -> Point { self.state.borrow().size }
+
+    fn set_size(&self, size: Point, style: &Style, _measurer: &TextMeasurer) {
+        let mut state = self.state.borrow_mut();
+        state.size = size;
+        state.reflow = false;
+        state.row_height = Dropdown::row_height(style);
+    }
+
+    fn size_request(&self, style: &Style, measurer: &TextMeasurer) -> Point {
+        let options = self.options.get();
+        let mut width: f32 = 0.;
+        for option in &options {
+            width = width.max(measurer.text_size(style, option).0)
+        }
+
+        let rows = if self.state.borrow().expanded { 1 + options.len() } else { 1 };
+        Point(width + Dropdown::padding(style) * 4. + Dropdown::arrow_size(style),
+              Dropdown::row_height(style) * rows as f32)
+    }
+
+    fn need_reflow(&self, style: &Style, measurer: &TextMeasurer) -> bool {
+        let Point(rw, rh) = self.size_request(style, measurer);
+        let Point(aw, ah) = self.size();
+        self.state.borrow().reflow || rw > aw || rh > ah
+    }
+
+    fn render(&self, nvg: &nanovg::Context, style: &Style) {
+        let state = self.state.borrow();
+
+        let (width, row_height, padding) =
+            (state.size.0, Dropdown::row_height(style), Dropdown::padding(style));
+        let inset = style.line_size / 2.;
+        let options = self.options.get();
+
+        nvg.stroke_width(style.line_size);
+        nvg.stroke_color(match state.ui_state {
+            _ if !self.enabled.get() => style.disabled_color,
+            State::Passive => style.passive_color,
+            State::Hovered | State::Active => style.hover_color
+        });
+        nvg.begin_path();
+        nvg.rounded_rect(inset, inset, width - inset * 2., row_height - inset * 2.,
+                         style.frame_corner_size / 2.);
+        nvg.stroke();
+
+        let arrow_size = Dropdown::arrow_size(style);
+        let arrow_x = width - padding * 2. - arrow_size;
+        let arrow_y = (row_height - arrow_size) / 2.;
+        let text_color =
+            if self.enabled.get() { style.active_color } else { style.disabled_color };
+        nvg.fill_color(text_color);
+        nvg.begin_path();
+        if state.expanded {
+            nvg.move_to(arrow_x, arrow_y + arrow_size);
+            nvg.line_to(arrow_x + arrow_size, arrow_y + arrow_size);
+            nvg.line_to(arrow_x + arrow_size / 2., arrow_y);
+        } else {
+            nvg.move_to(arrow_x, arrow_y);
+            nvg.line_to(arrow_x + arrow_size / 2., arrow_y + arrow_size);
+            nvg.line_to(arrow_x + arrow_size, arrow_y);
+        }
+        nvg.close_path();
+        nvg.fill();
+
+        nvg.font_face(&style.font_face);
+        nvg.font_size(style.font_size);
+        nvg.text_align(nanovg::LEFT | nanovg::MIDDLE);
+        if let Some(option) = options.get(self.selected.get()) {
+            nvg.text(padding * 2., row_height / 2., option);
+        }
+
+        if state.expanded {
+            for (index, option) in options.iter().enumerate() {
+                let top_y = row_height * (index + 1) as f32;
+                nvg.fill_color(if state.hovered_item == Some(index) {
+                    style.hover_color
+                } else {
+                    style.active_color
+                });
+                nvg.text(padding * 2., top_y + row_height / 2., option);
+            }
+        }
+    }
+
+    fn project(&self, point: Point) -> Option<(&Widget, Point)> {
+        Some((self, point))
+    }
+
+    fn tooltip(&self) -> Option<String> { self.tooltip.clone() }
+
+    fn mouse_in(&self) {
+        self.state.borrow_mut().ui_state = State::Hovered
+    }
+
+    fn mouse_move(&self, point: Point) {
+        let hovered_item = self.item_at(point);
+        let mut state = self.state.borrow_mut();
+        if state.expanded { state.hovered_item = hovered_item }
+    }
+
+    fn mouse_down(&self, _point: Point) {
+        self.state.borrow_mut().ui_state = State::Active
+    }
+
+    fn mouse_up(&self, point: Point) {
+        let (pressed, expanded) = {
+            let mut state = self.state.borrow_mut();
+            let pressed = state.ui_state == State::Active;
+            state.ui_state = State::Hovered;
+            (pressed, state.expanded)
+        };
+
+        if !pressed || !self.enabled.get() || !self.size().as_rect().contains(point) { return }
+
+        if expanded {
+            if let Some(index) = self.item_at(point) {
+                self.selected.set(index)
+            }
+            self.set_expanded(false)
+        } else {
+            self.set_expanded(true)
+        }
+    }
+
+    fn is_enabled(&self) -> bool { self.enabled.get() }
+
+    fn set_enabled(&self, enabled: bool) {
+        self.enabled.set(enabled);
+        if !enabled { self.set_expanded(false) }
+    }
+
+    fn mouse_out(&self) {
+        self.state.borrow_mut().ui_state = State::Passive;
+        self.set_expanded(false)
+    }
+}
+
+// TextInput
+
+pub struct TextInput {
+    state: RefCell<TextInputState>,
+    value: Rc<Property<f32>>,
+    enabled: Rc<Property<bool>>,
+    tooltip: Option<String>,
+}
+
+struct TextInputState {
+    size: Point,
+    ui_state: State,
+    editing: Option<String>,
+    invalid: bool,
+}
+
+impl TextInput {
+    pub fn new() -> TextInput {
+        TextInput {
+            state: RefCell::new(TextInputState {
+                size: Point(0., 0.),
+                ui_state: State::Passive,
+                editing: None,
+                invalid: false,
+            }),
+            value: Property::new(0.),
+            enabled: Property::new(true),
+            tooltip: None,
+        }
+    }
+
+    pub fn with_tooltip(self, tooltip: String) -> TextInput {
+        TextInput { tooltip: Some(tooltip), ..self }
+    }
+
+    pub fn value(&self) -> Rc<Property<f32>> { self.value.clone() }
+    pub fn enabled(&self) -> Rc<Property<bool>> { self.enabled.clone() }
+
+    fn padding(style: &Style) -> f32 { style.font_size / 4. }
+
+    fn commit(&self) {
+        let parsed = {
+            let state = self.state.borrow();
+            match state.editing {
+                Some(ref text) => text.trim().parse::<f32>().ok(),
+                None => return
+            }
+        };
+
+        match parsed {
+            Some(value) => {
+                self.value.set(value);
+                let mut state = self.state.borrow_mut();
+                state.editing = Some(format!("{}", self.value.get()));
+                state.invalid = false;
+            }
+            None => self.state.borrow_mut().invalid = true
+        }
+    }
+}
+
+impl Widget for TextInput {
+    fn size(&self) -> Point { self.state.borrow().size }
+    fn set_size(&self, size: Point, _style: &Style, _measurer: &TextMeasurer) {
+        self.state.borrow_mut().size = size
+    }
+
+    fn size_request(&self, style: &Style, measurer: &TextMeasurer) -> Point {
+        measurer.text_size(style, "00000000") +
+            Point(TextInput::padding(style), TextInput::padding(style)) * 2.
+    }
+
+    fn render(&self, nvg: &nanovg::Context, style: &Style) {
+        let state = self.state.borrow();
+
+        let inset = style.line_size / 4.;
+        let Point(w, h) = state.size;
+        nvg.stroke_width(style.line_size / 2.);
+        nvg.stroke_color(if state.invalid {
+            style.error_color
+        } else {
+            match state.ui_state {
+                _ if !self.enabled.get() => style.disabled_color,
+                State::Passive => style.passive_color,
+                State::Hovered | State::Active => style.hover_color
+            }
+        });
+        nvg.begin_path();
+        nvg.rect(inset, inset, w - inset * 2., h - inset * 2.);
+        nvg.stroke();
+
+        let text = match state.editing {
+            Some(ref text) => text.clone(),
+            None => format!("{}", self.value.get())
+        };
+
+        nvg.font_face(&style.font_face);
+        nvg.font_size(style.font_size);
+        nvg.fill_color(if state.invalid {
+            style.error_color
+        } else if !self.enabled.get() {
+            style.disabled_color
+        } else {
+            style.active_color
+        });
+        nvg.text_align(nanovg::LEFT | nanovg::MIDDLE);
+        let caret_x = nvg.text(TextInput::padding(style), h / 2., &text);
+
+        if state.editing.is_some() {
+            nvg.stroke_color(style.active_color);
+            nvg.begin_path();
+            nvg.move_to(caret_x, TextInput::padding(style));
+            nvg.line_to(caret_x, h - TextInput::padding(style));
+            nvg.stroke();
+        }
+    }
+
+    fn project(&self, point: Point) -> Option<(&Widget, Point)> {
+        Some((self, point))
+    }
+
+    fn tooltip(&self) -> Option<String> { self.tooltip.clone() }
+
+    fn mouse_in(&self) {
+        self.state.borrow_mut().ui_state = State::Hovered
+    }
+
+    fn mouse_out(&self) {
+        self.state.borrow_mut().ui_state = State::Passive
+    }
+
+    fn is_enabled(&self) -> bool { self.enabled.get() }
+    fn set_enabled(&self, enabled: bool) { self.enabled.set(enabled) }
+
+    fn accepts_focus(&self) -> bool { self.enabled.get() }
+
+    fn focus_in(&self) {
+        let mut state = self.state.borrow_mut();
+        state.editing = Some(format!("{}", self.value.get()));
+        state.invalid = false;
+    }
+
+    fn focus_out(&self) {
+        let mut state = self.state.borrow_mut();
+        state.editing = None;
+        state.invalid = false;
+    }
+
+    fn key_input(&self, key: glfw::Key, _modifiers: glfw::Modifiers) {
+        if !self.enabled.get() { return }
+        match key {
+            glfw::Key::Backspace => {
+                let mut state = self.state.borrow_mut();
+                if let Some(ref mut text) = state.editing { text.pop(); }
+                state.invalid = false;
+            }
+            glfw::Key::Enter | glfw::Key::KpEnter =>
+                self.commit(),
+            _ => ()
+        }
+    }
+
+    fn char_input(&self, character: char) {
+        if !self.enabled.get() { return }
+        if !(character.is_digit(10) || character == '.' || character == '-') { return }
+
+        let mut state = self.state.borrow_mut();
+        if let Some(ref mut text) = state.editing { text.push(character) }
+        state.invalid = false;
+    }
+}
+
+// TextEntry
+
+/// Like `TextInput`, but for free-form text, such as names.
+pub struct TextEntry {
+    state: RefCell<TextEntryState>,
+    text: Rc<Property<String>>,
+    tooltip: Option<String>,
+}
+
+struct TextEntryState {
+    size: Point,
+    ui_state: State,
+    editing: Option<String>,
+}
+
+impl TextEntry {
+    pub fn new() -> TextEntry {
+        TextEntry {
+            state: RefCell::new(TextEntryState {
+                size: Point(0., 0.),
+                ui_state: State::Passive,
+                editing: None,
+            }),
+            text: Property::new(String::new()),
+            tooltip: None,
+        }
+    }
+
+    pub fn with_tooltip(self, tooltip: String) -> TextEntry {
+        TextEntry { tooltip: Some(tooltip), ..self }
+    }
+
+    pub fn text(&self) -> Rc<Property<String>> { self.text.clone() }
+
+    fn padding(style: &Style) -> f32 { style.font_size / 4. }
+
+    fn commit(&self) {
+        let text = match self.state.borrow().editing {
+            Some(ref text) => text.clone(),
+            None => return
+        };
+        self.text.set(text)
+    }
+}
+
+impl Widget for TextEntry {
+    fn size(&self) -> Point { self.state.borrow().size }
+    fn set_size(&self, size: Point, _style: &Style, _measurer: &TextMeasurer) {
+        self.state.borrow_mut().size = size
+    }
+
+    fn size_request(&self, style: &Style, measurer: &TextMeasurer) -> Point {
+        measurer.text_size(style, "0000000000000000") +
+            Point(TextEntry::padding(style), TextEntry::padding(style)) * 2.
+    }
+
+    fn render(&self, nvg: &nanovg::Context, style: &Style) {
+        let state = self.state.borrow();
+
+        let inset = style.line_size / 4.;
+        let Point(w, h) = state.size;
+        nvg.stroke_width(style.line_size / 2.);
+        nvg.stroke_color(match state.ui_state {
+            State::Passive => style.passive_color,
+            State::Hovered | State::Active => style.hover_color
+        });
+        nvg.begin_path();
+        nvg.rect(inset, inset, w - inset * 2., h - inset * 2.);
+        nvg.stroke();
+
+        let text = match state.editing {
+            Some(ref text) => text.clone(),
+            None => self.text.get()
+        };
+
+        nvg.save();
+        nvg.intersect_scissor(inset, inset, w - inset * 2., h - inset * 2.);
+        nvg.font_face(&style.font_face);
+        nvg.font_size(style.font_size);
+        nvg.fill_color(style.active_color);
+        nvg.text_align(nanovg::LEFT | nanovg::MIDDLE);
+        let caret_x = nvg.text(TextEntry::padding(style), h / 2., &text);
+
+        if state.editing.is_some() {
+            nvg.stroke_color(style.active_color);
+            nvg.begin_path();
+            nvg.move_to(caret_x, TextEntry::padding(style));
+            nvg.line_to(caret_x, h - TextEntry::padding(style));
+            nvg.stroke();
+        }
+        nvg.restore();
+    }
+
+    fn project(&self, point: Point) -> Option<(&Widget, Point)> {
+        Some((self, point))
+    }
+
+    fn tooltip(&self) -> Option<String> { self.tooltip.clone() }
+
+    fn mouse_in(&self) {
+        self.state.borrow_mut().ui_state = State::Hovered
+    }
+
+    fn mouse_out(&self) {
+        self.state.borrow_mut().ui_state = State::Passive
+    }
+
+    fn accepts_focus(&self) -> bool { true }
+
+    fn focus_in(&self) {
+        self.state.borrow_mut().editing = Some(self.text.get())
+    }
+
+    fn focus_out(&self) {
+        // Unlike a number, text can't be invalid, so it is kept when focus moves away.
+        self.commit();
+        self.state.borrow_mut().editing = None
+    }
+
+    fn key_input(&self, key: glfw::Key, _modifiers: glfw::Modifiers) {
+        match key {
+            glfw::Key::Backspace => {
+                if let Some(ref mut text) = self.state.borrow_mut().editing { text.pop(); }
+                self.commit()
+            }
+            glfw::Key::Enter | glfw::Key::KpEnter =>
+                self.commit(),
+            _ => ()
+        }
+    }
+
+    fn char_input(&self, character: char) {
+        if character.is_control() { return }
+
+        if let Some(ref mut text) = self.state.borrow_mut().editing { text.push(character) }
+        // Buttons don't take focus, so clicking one right after typing would otherwise
+        // act on the previous text.
+        self.commit()
+    }
+}
+
+// Histogram
+
+#[derive(Clone, Debug, Default)]
+pub struct HistogramBins {
+    pub red: Vec<u32>,
+    pub green: Vec<u32>,
+    pub blue: Vec<u32>,
+    pub luma: Vec<u32>,
+}
+
+impl HistogramBins {
+    /// Computes histograms of an RGBA8 buffer, looking only at every `stride`th pixel.
+    pub fn compute(rgba: &[u8], stride: usize) -> HistogramBins {
+        let mut bins = HistogramBins {
+            red: vec![0; 256],
+            green: vec![0; 256],
+            blue: vec![0; 256],
+            luma: vec![0; 256],
+        };
+
+        let step = 4 * cmp::max(stride, 1);
+        let mut index = 0;
+        while index + 4 <= rgba.len() {
+            let (r, g, b) = (rgba[index], rgba[index + 1], rgba[index + 2]);
+            bins.red[r as usize] += 1;
+            bins.green[g as usize] += 1;
+            bins.blue[b as usize] += 1;
+            // Rec. 709 luma coefficients, in 8-bit fixed point.
+            let luma = (54 * r as u32 + 183 * g as u32 + 19 * b as u32) >> 8;
+            bins.luma[luma as usize] += 1;
+            index += step
+        }
+
+        bins
+    }
+
+    fn peak(&self) -> u32 {
+        [&self.red, &self.green, &self.blue, &self.luma].iter()
+            .flat_map(|bins| bins.iter())
+            .fold(0, |acc, &count| cmp::max(acc, count))
+    }
+}
+
+pub struct Histogram {
+    state: RefCell<HistogramState>,
+    bins: Rc<Property<HistogramBins>>,
+}
+
+struct HistogramState {
+    size: Point,
+}
+
+impl Histogram {
+    pub fn new() -> Histogram {
+        Histogram {
+            state: RefCell::new(HistogramState {
+                size: Point(0., 0.),
+            }),
+            bins: Property::new(HistogramBins::default()),
+        }
+    }
+
+    pub fn bins(&self) -> Rc<Property<HistogramBins>> { self.bins.clone() }
+
+    fn trace(&self, nvg: &nanovg::Context, bins: &[u32], peak: u32) {
+        let Point(w, h) = self.size();
+        nvg.begin_path();
+        nvg.move_to(0., h);
+        for (index, &count) in bins.iter().enumerate() {
+            nvg.line_to(w * index as f32 / (bins.len() - 1) as f32,
+                        h - h * count as f32 / peak as f32);
+        }
+        nvg.line_to(w, h);
+    }
+}
+
+impl Widget for Histogram {
+    fn size(&self) -> Point { self.state.borrow().size }
+    fn set_size(&self, size: Point, _style: &Style, _measurer: &TextMeasurer) {
+        self.state.borrow_mut().size = size
+    }
+
+    fn size_request(&self, style: &Style, _measurer: &TextMeasurer) -> Point {
+        Point(style.font_size * 10., style.font_size * 3.)
+    }
+
+    fn render(&self, nvg: &nanovg::Context, style: &Style) {
+        let bins = self.bins.get();
+        let peak = bins.peak();
+        if peak == 0 { return }
+
+        self.trace(nvg, &bins.luma, peak);
+        nvg.close_path();
+        nvg.fill_color(style.passive_color);
+        nvg.fill();
+        nvg.stroke_width(style.line_size / 2.);
+        nvg.stroke_color(style.active_color);
+        nvg.stroke();
+
+        nvg.stroke_width(style.line_size / 4.);
+        for &(channel, color) in &[(&bins.red,   nanovg::Color::rgb_f(1., 0.3, 0.3)),
+                                   (&bins.green, nanovg::Color::rgb_f(0.3, 1., 0.3)),
+                                   (&bins.blue,  nanovg::Color::rgb_f(0.3, 0.3, 1.))] {
+            self.trace(nvg, channel, peak);
+            nvg.stroke_color(color);
+            nvg.stroke();
+        }
+    }
+}
+
+// Spinner
+
+pub struct Spinner {
+    state: RefCell<SpinnerState>,
+    active: Rc<Property<bool>>,
+}
+
+struct SpinnerState {
+    size: Point,
+    angle: f32,
+}
+
+impl Spinner {
+    pub fn new() -> Spinner {
+        Spinner {
+            state: RefCell::new(SpinnerState {
+                size: Point(0., 0.),
+                angle: 0.,
+            }),
+            active: Property::new(false),
+        }
+    }
+
+    /// Whether the spinner is shown; it takes up space even when it is not.
+    pub fn active(&self) -> Rc<Property<bool>> { self.active.clone() }
+
+    fn speed() -> f64 { 1.5 } // revolutions per second
+}
+
+impl Widget for Spinner {
+    fn size(&self) -> Point { self.state.borrow().size }
+    fn set_size(&self, size: Point, _style: &Style, _measurer: &TextMeasurer) {
+        self.state.borrow_mut().size = size
+    }
+
+    fn size_request(&self, style: &Style, _measurer: &TextMeasurer) -> Point {
+        Point(style.font_size, style.font_size)
+    }
+
+    fn prepare(&self, time: f64) {
+        let revolutions = (time * Spinner::speed()).fract();
+        self.state.borrow_mut().angle = (revolutions * 2. * f64::consts::PI) as f32
+    }
+
+    fn animating(&self) -> bool { self.active.get() }
+
+    fn render(&self, nvg: &nanovg::Context, style: &Style) {
+        if !self.active.get() { return }
+
+        let state = self.state.borrow();
+        let Point(w, h) = state.size;
+        let radius = (w.min(h) - style.line_size) / 2.;
+
+        nvg.begin_path();
+        nvg.arc(w / 2., h / 2., radius, state.angle, state.angle + f32::consts::PI * 1.5,
+                nanovg::Winding::CW);
+        nvg.stroke_width(style.line_size / 2.);
+        nvg.stroke_color(style.hover_color);
+        nvg.stroke();
+    }
+}
+
+// ProgressBar
+
+pub struct ProgressBar {
+    state: RefCell<ProgressBarState>,
+    value: Rc<Property<f32>>,
+}
+
+struct ProgressBarState {
+    size: Point,
+}
+
+impl ProgressBar {
+    pub fn new() -> ProgressBar {
+        ProgressBar {
+            state: RefCell::new(ProgressBarState {
+                size: Point(0., 0.),
+            }),
+            value: Property::with_validator(0., |value| *value = value.max(0.).min(1.)),
+        }
+    }
+
+    /// Fraction of the work done, from 0 to 1.
+    pub fn value(&self) -> Rc<Property<f32>> { self.value.clone() }
+}
+
+impl Widget for ProgressBar {
+    fn size(&self) -> Point { self.state.borrow().size }
+    fn set_size(&self, size: Point, _style: &Style, _measurer: &TextMeasurer) {
+        self.state.borrow_mut().size = size
+    }
+
+    fn size_request(&self, style: &Style, _measurer: &TextMeasurer) -> Point {
+        Point(style.font_size * 10., style.font_size / 2.)
+    }
+
+    fn render(&self, nvg: &nanovg::Context, style: &Style) {
+        let Point(w, h) = self.size();
+        let value = self.value.get();
+
+        nvg.begin_path();
+        nvg.rounded_rect(0., 0., w, h, h / 2.);
+        nvg.fill_color(style.passive_color);
+        nvg.fill();
+
+        if value > 0. {
+            nvg.begin_path();
+            nvg.rounded_rect(0., 0., (w * value).max(h), h, h / 2.);
+            nvg.fill_color(style.hover_color);
+            nvg.fill();
+        }
+    }
+}
+
+// Separator
+
+pub struct Separator {
+    direction: Direction,
+    state: RefCell<SeparatorState>,
+}
+
+struct SeparatorState {
+    size: Point,
+}
+
+impl Separator {
+    /// Creates a separator that is a horizontal or a vertical line.
+    pub fn new(direction: Direction) -> Separator {
+        Separator {
+            direction: direction,
+            state: RefCell::new(SeparatorState {
+                size: Point(0., 0.),
+            })
+        }
+    }
+
+    fn thickness(style: &Style) -> f32 { style.line_size * 2. }
+}
+
+impl Widget for Separator {
+    fn size(&self) -> Point { self.state.borrow().size }
+    fn set_size(&self, size: Point, _style: &Style, _measurer: &TextMeasurer) {
+        self.state.borrow_mut().size = size
+    }
+
+    fn size_request(&self, style: &Style, _measurer: &TextMeasurer) -> Point {
+        match self.direction {
+            Direction::Horizontal => Point(0., Separator::thickness(style)),
+            Direction::Vertical   => Point(Separator::thickness(style), 0.)
+        }
+    }
+
+    fn render(&self, nvg: &nanovg::Context, style: &Style) {
+        let Point(w, h) = self.size();
+
+        nvg.begin_path();
+        match self.direction {
+            Direction::Horizontal => {
+                nvg.move_to(0., h / 2.);
+                nvg.line_to(w, h / 2.);
+            }
+            Direction::Vertical => {
+                nvg.move_to(w / 2., 0.);
+                nvg.line_to(w / 2., h);
+            }
+        }
+        nvg.stroke_width(style.line_size / 4.);
+        nvg.stroke_color(style.passive_color);
+        nvg.stroke();
+    }
+}
+
+// Spacer
+
+pub struct Spacer {
+    request: Point,
+    size: Cell<Point>,
+}
+
+impl Spacer {
+    pub fn new(request: Point) -> Spacer {
+        Spacer { request: request, size: Cell::new(Point(0., 0.)) }
+    }
+}
+
+impl Widget for Spacer {
+    fn size(&self) -> Point { self.size.get() }
+    fn set_size(&self, size: Point, _style: &Style, _measurer: &TextMeasurer) {
+        self.size.set(size)
+    }
+    fn size_request(&self, _style: &Style, _measurer: &TextMeasurer) -> Point { self.request }
+    fn render(&self, _nvg: &nanovg::Context, _style: &Style) {}
+}
+
+// Menu
+
+pub struct Menu {
+    items: Vec<String>,
+    state: RefCell<MenuState>,
+}
+
+struct MenuState {
+    size: Point,
+    position: Point,
+    hovered_item: Option<usize>,
+    // Padding and row height as of the last layout, for matching input to items.
+    padding: f32,
+    row_height: f32,
+}
+
+impl Menu {
+    pub fn new(items: Vec<String>, position: Point) -> Menu {
+        Menu {
+            items: items,
+            state: RefCell::new(MenuState {
+                size: Point(0., 0.),
+                position: position,
+                hovered_item: None,
+                padding: 0.,
+                row_height: 0.,
+            })
+        }
+    }
+
+    pub fn position(&self) -> Point { self.state.borrow().position }
+
+    pub fn item_at(&self, point: Point) -> Option<usize> {
+        if !self.size().as_rect().contains(point) { return None }
+
+        let (padding, row_height) = {
+            let state = self.state.borrow();
+            (state.padding, state.row_height)
+        };
+        let row = ((point.1 - padding) / row_height).floor();
+        if row >= 0. && (row as usize) < self.items.len() {
+            Some(row as usize)
+        } else {
+            None
+        }
+    }
+
+    fn padding(style: &Style) -> f32 { style.font_size / 4. }
+    fn row_height(style: &Style) -> f32 { style.font_size + Menu::padding(style) }
+}
+
+impl Widget for Menu {
+    fn size(&self) -> Point { self.state.borrow().size }
+    fn set_size(&self, size: Point, style: &Style, _measurer: &TextMeasurer) {
+        let mut state = self.state.borrow_mut();
+        state.size = size;
+        state.padding = Menu::padding(style);
+        state.row_height = Menu::row_height(style);
+    }
+
+    fn size_request(&self, style: &Style, measurer: &TextMeasurer) -> Point {
+        let mut width: f32 = 0.;
+        for item in &self.items {
+            width = width.max(measurer.text_size(style, item).0)
+        }
+
+        Point(width + Menu::padding(style) * 4.,
+              Menu::row_height(style) * self.items.len() as f32 + Menu::padding(style))
+    }
+
+    fn render(&self, nvg: &nanovg::Context, style: &Style) {
+        let state = self.state.borrow();
+        let Point(w, h) = state.size;
+
+        nvg.begin_path();
+        nvg.rounded_rect(0., 0., w, h, style.frame_corner_size / 2.);
+        nvg.fill_color(style.background_color);
+        nvg.fill();
+        nvg.stroke_width(style.line_size / 2.);
+        nvg.stroke_color(style.passive_color);
+        nvg.stroke();
+
+        nvg.font_face(&style.font_face);
+        nvg.font_size(style.font_size);
+        nvg.text_align(nanovg::LEFT | nanovg::TOP);
+        for (index, item) in self.items.iter().enumerate() {
+            nvg.fill_color(if state.hovered_item == Some(index) {
+                style.hover_color
+            } else {
+                style.active_color
+            });
+            nvg.text(Menu::padding(style) * 2.,
+                     Menu::padding(style) + Menu::row_height(style) * index as f32, item);
+        }
+    }
+
+    fn project(&self, point: Point) -> Option<(&Widget, Point)> {
+        Some((self, point))
+    }
+
+    fn mouse_move(&self, point: Point) {
+        let hovered_item = self.item_at(point);
+        self.state.borrow_mut().hovered_item = hovered_item
+    }
+
+    fn mouse_out(&self) {
+        self.state.borrow_mut().hovered_item = None
+    }
+}
+
+// BoxLayout
+
+pub struct BoxLayout<'nvg> {
+    direction: Direction,
+    alignment: Alignment,
+    spacing: f32,
+    padding: Point,
+    children: Vec<Box<Widget + 'nvg>>,
+    weights: Vec<Option<f32>>,
+    state: RefCell<BoxLayoutState>,
+}
+
+struct BoxLayoutState {
+    size: Point,
+}
+
+impl<'nvg> BoxLayout<'nvg> {
+    pub fn new(dir: Direction) -> BoxLayout<'nvg> {
+        BoxLayout {
+            direction: dir,
+            alignment: Alignment::Stretch,
+            spacing: 0.,
+            padding: Point(0., 0.),
+            children: Vec::new(),
+            weights: Vec::new(),
+            state: RefCell::new(BoxLayoutState {
+                size: Point(0., 0.),
+            })
+        }
+    }
+
+    pub fn horz() -> BoxLayout<'nvg> {
+        BoxLayout::new(Direction::Horizontal)
+    }
+
+    pub fn vert() -> BoxLayout<'nvg> {
+        BoxLayout::new(Direction::Vertical)
+    }
+
+    pub fn set_spacing(&mut self, spacing: f32) {
+        self.spacing = spacing
+    }
+
+    pub fn set_padding(&mut self, padding: Point) {
+        self.padding = padding
+    }
+
+    pub fn set_alignment(&mut self, alignment: Alignment) {
+        self.alignment = alignment
+    }
+
+    /// Adds a child that gets `weight` of the space left after every child got its size
+    /// request; with 0, it stays at its requested size. Children added with `add` have
+    /// a weight proportional to their size request, with all such weights adding up to 1.
+    pub fn add_weighted(&mut self, widget: Box<Widget + 'nvg>, weight: f32) {
+        self.children.push(widget);
+        self.weights.push(Some(weight))
+    }
+
+    fn cross_size(&self, request: f32, available: f32) -> f32 {
+        match self.alignment {
+            Alignment::Stretch => available,
+            _ => request.min(available)
+        }
+    }
+
+    fn cross_offset(&self, size: f32, available: f32) -> f32 {
+        match self.alignment {
+            Alignment::Start | Alignment::Stretch => 0.,
+            Alignment::Center => (available - size) / 2.,
+            Alignment::End => available - size
+        }
+    }
+
+    // Space taken by padding and spacing, which is not distributed between children.
+    fn overhead(&self) -> Point {
+        let spacing = self.spacing * self.children.len().saturating_sub(1) as f32;
+        self.padding * 2. + match self.direction {
+            Direction::Horizontal => Point(spacing, 0.),
+            Direction::Vertical   => Point(0., spacing)
+        }
+    }
+
+    fn main_axis(&self, point: Point) -> f32 {
+        match self.direction {
+            Direction::Horizontal => point.0,
+            Direction::Vertical   => point.1
+        }
+    }
+
+    fn cross_axis(&self, point: Point) -> f32 {
+        match self.direction {
+            Direction::Horizontal => point.1,
+            Direction::Vertical   => point.0
+        }
+    }
+
+    fn from_axes(&self, main: f32, cross: f32) -> Point {
+        match self.direction {
+            Direction::Horizontal => Point(main, cross),
+            Direction::Vertical   => Point(cross, main)
+        }
+    }
+
+    // Gives every child its size request along the main axis, and splits the rest of
+    // `available` space by weight; children whose share violates their limits get
+    // the limit instead, and the rest is split again between the others.
+    fn distribute(&self, available: f32, style: &Style, measurer: &TextMeasurer) -> Vec<f32> {
+        let requests = self.children.iter().map(|child|
+            self.main_axis(child.size_request(style, measurer))).collect::<Vec<_>>();
+        let total_request = requests.iter().fold(0., |acc, request| acc + request);
+        let weights = self.weights.iter().zip(requests.iter()).map(|(weight, &request)|
+            weight.unwrap_or(if total_request > 0. { request / total_request } else { 0. }))
+            .collect::<Vec<_>>();
+        let limits = self.children.iter().map(|child|
+            (child.min_size(style).map_or(0., |size| self.main_axis(size)),
+             child.max_size(style).map_or(f32::INFINITY, |size| self.main_axis(size))))
+            .collect::<Vec<_>>();
+
+        let mut fixed: Vec<Option<f32>> = vec![None; self.children.len()];
+        loop {
+            let fixed_size = fixed.iter().fold(0., |acc, size| acc + size.unwrap_or(0.));
+            let (flexible_request, flexible_weight) = fixed.iter().enumerate()
+                .filter(|&(_, size)| size.is_none())
+                .fold((0., 0.), |(request, weight), (index, _)|
+                    (request + requests[index], weight + weights[index]));
+            let extra = available - fixed_size - flexible_request;
+            let share = |index: usize|
+                if flexible_weight > 0. {
+                    (requests[index] + extra * weights[index] / flexible_weight).max(0.)
+                } else {
+                    requests[index]
+                };
+
+            let mut changed = false;
+            for (index, size) in fixed.iter_mut().enumerate() {
+                if size.is_some() { continue }
+
+                let (min, max) = limits[index];
+                let child_size = share(index);
+                if child_size < min {
+                    *size = Some(min);
+                    changed = true
+                } else if child_size > max {
+                    *size = Some(max);
+                    changed = true
+                }
+            }
+
+            if !changed {
+                return fixed.iter().enumerate().map(|(index, size)|
+                    size.unwrap_or_else(|| share(index))).collect()
+            }
+        }
+    }
+
+    fn child_origins(&self) -> Vec<Point> {
+        let available = self.size() - self.padding * 2.;
+        let mut origin = self.padding;
+        self.children.iter().map(|child| {
+            let size = child.size();
+            match self.direction {
+                Direction::Horizontal => {
+                    let child_origin =
+                        Point(origin.0, origin.1 + self.cross_offset(size.1, available.1));
+                    origin.0 += size.0 + self.spacing;
+                    child_origin
+                },
+                Direction::Vertical => {
+                    let child_origin =
+                        Point(origin.0 + self.cross_offset(size.0, available.0), origin.1);
+                    origin.1 += size.1 + self.spacing;
+                    child_origin
+                }
+            }
+        }).collect()
+    }
+}
+
+impl<'nvg> Widget for BoxLayout<'nvg> {
+    fn size(&self) -> Point { self.state.borrow().size }
+
+    fn set_size(&self, size: Point, style: &Style, measurer: &TextMeasurer) {
+        self.state.borrow_mut().size = size;
+
+        let size = size - self.overhead();
+        let main_sizes = self.distribute(self.main_axis(size), style, measurer);
+        for (child, main_size) in self.children.iter().zip(main_sizes) {
+            let cross_request = self.cross_axis(child.size_request(style, measurer));
+            let (min, max) =
+                (child.min_size(style).map_or(0., |size| self.cross_axis(size)),
+                 child.max_size(style).map_or(f32::INFINITY, |size| self.cross_axis(size)));
+            let cross_size = self.cross_size(cross_request, self.cross_axis(size));
+            child.set_size(self.from_axes(main_size, cross_size.max(min).min(max)), style,
+                           measurer);
+        }
+    }
+
+    fn size_request(&self, style: &Style, measurer: &TextMeasurer) -> Point {
+        let requests = self.children.iter().
+            map(|child| { child.size_request(style, measurer) }).collect::<Vec<_>>();
+        let xs = requests.iter().map(|req| { req.0 });
+        let ys = requests.iter().map(|req| { req.1 });
+
+        let content = match self.direction {
+            Direction::Horizontal =>
+                Point(xs.sum(), ys.fold(0., |l, r| { l.max(r) })),
+            Direction::Vertical =>
+                Point(xs.fold(0., |l, r| { l.max(r) }), ys.sum())
+        };
+        content + self.overhead()
+    }
+
+    fn prepare(&self, time: f64) {
+        for child in &self.children { child.prepare(time) }
+    }
+
+    fn set_enabled(&self, enabled: bool) {
+        for child in &self.children { child.set_enabled(enabled) }
+    }
+
+    fn animating(&self) -> bool {
+        self.children.iter().any(|child| child.animating())
+    }
+
+    fn children<'a>(&'a self) -> Vec<&'a Widget> {
+        self.children.iter().map(|child| &**child as &Widget).collect()
+    }
+
+    fn need_reflow(&self, style: &Style, measurer: &TextMeasurer) -> bool {
+        self.children.iter().fold(false, |acc, child| {
+            acc || child.need_reflow(style, measurer)
+        })
+    }
+
+    fn render(&self, nvg: &nanovg::Context, style: &Style) {
+        for (child, origin) in self.children.iter().zip(self.child_origins()) {
+            let Point(x, y) = origin;
+            let Point(w, h) = child.size();
+
+            nvg.save();
+            nvg.translate(x, y);
+            // Intersect, so that a ScrollView further up still clips its contents.
+            nvg.intersect_scissor(0., 0., w, h);
+            child.render(nvg, style);
+            nvg.restore();
+        }
+    }
+
+    fn project(&self, point: Point) -> Option<(&Widget, Point)> {
+        for (child, origin) in self.children.iter().zip(self.child_origins()) {
+            if Rect(origin, child.size()).contains(point) {
+                return child.project(point - origin)
+            }
+        }
+
+        None
+    }
+}
+
+impl<'nvg> Container<'nvg> for BoxLayout<'nvg> {
+    fn add(&mut self, widget: Box<Widget + 'nvg>) {
+        self.children.push(widget);
+        self.weights.push(None)
+    }
+
+    fn remove(&mut self, widget: &Widget) -> Box<Widget + 'nvg> {
+        let index = self.iter().position(|elem| { elem.is(widget) }).unwrap();
+        self.weights.remove(index);
+        self.children.remove(index)
+    }
+
+    fn iter<'a>(&'a self) -> Iter<'a> {
+        Iter { elements: &self.children, index: 0 }
+    }
+}
+
+// GridLayout
+
+pub struct GridLayout<'nvg> {
+    columns: usize,
+    children: Vec<Box<Widget + 'nvg>>,
+    state: RefCell<GridLayoutState>,
+}
+
+struct GridLayoutState {
+    size: Point,
+    cell_width: f32,
+    row_heights: Vec<f32>,
+}
+
+impl<'nvg> GridLayout<'nvg> {
+    pub fn new(columns: usize) -> GridLayout<'nvg> {
+        GridLayout {
+            columns: if columns == 0 { 1 } else { columns },
+            children: Vec::new(),
+            state: RefCell::new(GridLayoutState {
+                size: Point(0., 0.),
+                cell_width: 0.,
+                row_heights: Vec::new(),
+            })
+        }
+    }
+
+    fn requests(&self, style: &Style, measurer: &TextMeasurer) -> (f32, Vec<f32>) {
+        let mut cell_width: f32 = 0.;
+        let mut row_heights = Vec::new();
+        for row in self.children.chunks(self.columns) {
+            let mut row_height: f32 = 0.;
+            for child in row {
+                let Point(w, h) = child.size_request(style, measurer);
+                cell_width = cell_width.max(w);
+                row_height = row_height.max(h);
+            }
+            row_heights.push(row_height)
+        }
+        (cell_width, row_heights)
+    }
+
+    fn cell_origin(&self, index: usize) -> Point {
+        let state = self.state.borrow();
+        let (row, column) = (index / self.columns, index % self.columns);
+        Point(state.cell_width * column as f32,
+              state.row_heights.iter().take(row).fold(0., |acc, h| acc + h))
+    }
+}
+
+impl<'nvg> Widget for GridLayout<'nvg> {
+    fn size(&self) -> Point { self.state.borrow().size }
+
+    fn set_size(&self, size: Point, style: &Style, measurer: &TextMeasurer) {
+        let (_, requests) = self.requests(style, measurer);
+        let request = self.size_request(style, measurer);
+
+        let cell_width = size.0 / self.columns as f32;
+        let row_heights = requests.iter().map(|height| {
+            if request.1 > 0. { height * size.1 / request.1 } else { 0. }
+        }).collect::<Vec<_>>();
+
+        for (index, child) in self.children.iter().enumerate() {
+            child.set_size(Point(cell_width, row_heights[index / self.columns]), style,
+                           measurer)
+        }
+
+        let mut state = self.state.borrow_mut();
+        state.size = size;
+        state.cell_width = cell_width;
+        state.row_heights = row_heights;
+    }
+
+    fn size_request(&self, style: &Style, measurer: &TextMeasurer) -> Point {
+        let (cell_width, row_heights) = self.requests(style, measurer);
+        Point(cell_width * self.columns as f32, row_heights.iter().fold(0., |acc, h| acc + h))
+    }
+
+    fn prepare(&self, time: f64) {
+        for child in &self.children { child.prepare(time) }
+    }
+
+    fn set_enabled(&self, enabled: bool) {
+        for child in &self.children { child.set_enabled(enabled) }
+    }
+
+    fn animating(&self) -> bool {
+        self.children.iter().any(|child| child.animating())
+    }
+
+    fn children<'a>(&'a self) -> Vec<&'a Widget> {
+        self.children.iter().map(|child| &**child as &Widget).collect()
+    }
+
+    fn need_reflow(&self, style: &Style, measurer: &TextMeasurer) -> bool {
+        self.children.iter().fold(false, |acc, child| {
+            acc || child.need_reflow(style, measurer)
+        })
+    }
+
+    fn render(&self, nvg: &nanovg::Context, style: &Style) {
+        for (index, child) in self.children.iter().enumerate() {
+            let Point(x, y) = self.cell_origin(index);
+            let Point(w, h) = child.size();
+
+            nvg.save();
+            nvg.translate(x, y);
+            // Intersect, so that a ScrollView further up still clips its contents.
+            nvg.intersect_scissor(0., 0., w, h);
+            child.render(nvg, style);
+            nvg.restore();
+        }
+    }
+
+    fn project(&self, point: Point) -> Option<(&Widget, Point)> {
+        for (index, child) in self.children.iter().enumerate() {
+            let origin = self.cell_origin(index);
+            if Rect(origin, child.size()).contains(point) {
+                return child.project(point - origin)
+            }
+        }
+
+        None
+    }
+}
+
+impl<'nvg> Container<'nvg> for GridLayout<'nvg> {
+    fn add(&mut self, widget: Box<Widget + 'nvg>) {
+        self.children.push(widget)
+    }
+
+    fn remove(&mut self, widget: &Widget) -> Box<Widget + 'nvg> {
+        let index = self.iter().position(|elem| { elem.is(widget) });
+        self.children.remove(index.unwrap())
+    }
+
+    fn iter<'a>(&'a self) -> Iter<'a> {
+        Iter { elements: &self.children, index: 0 }
+    }
+}
+
+// ScrollView
+
+pub struct ScrollView<'nvg> {
+    widget: Box<Widget + 'nvg>,
+    max_height: f32,
+    state: RefCell<ScrollViewState>,
+}
+
+struct ScrollViewState {
+    size: Point,
+    offset: f32,
+    // Distance scrolled by a notch of the wheel, as of the last layout.
+    scroll_step: f32,
+}
+
+impl<'nvg> ScrollView<'nvg> {
+    pub fn new(widget: Box<Widget + 'nvg>) -> ScrollView<'nvg> {
+        ScrollView {
+            widget: widget,
+            max_height: f32::INFINITY,
+            state: RefCell::new(ScrollViewState {
+                size: Point(0., 0.),
+                offset: 0.,
+                scroll_step: 0.,
+            })
+        }
+    }
+
+    /// Limits the height requested by the view; the contents scroll if they are taller.
+    pub fn set_max_height(&mut self, max_height: f32) {
+        self.max_height = max_height
+    }
+
+    fn scrollbar_width(style: &Style) -> f32 { style.line_size * 2. }
+    fn scroll_step(style: &Style) -> f32 { style.font_size * 2. }
+
+    fn max_offset(&self) -> f32 {
+        (self.widget.size().1 - self.size().1).max(0.)
+    }
+}
+
+impl<'nvg> Widget for ScrollView<'nvg> {
+    fn size(&self) -> Point { self.state.borrow().size }
+
+    fn set_size(&self, size: Point, style: &Style, measurer: &TextMeasurer) {
+        let Point(_, child_h) = self.widget.size_request(style, measurer);
+        let child_w = size.0 - ScrollView::scrollbar_width(style);
+        self.widget.set_size(Point(child_w, child_h.max(size.1)), style, measurer);
+
+        let max_offset = self.max_offset();
+        let mut state = self.state.borrow_mut();
+        state.size = size;
+        state.offset = state.offset.min(max_offset);
+        state.scroll_step = ScrollView::scroll_step(style)
+    }
+
+    fn size_request(&self, style: &Style, measurer: &TextMeasurer) -> Point {
+        let Point(w, h) = self.widget.size_request(style, measurer);
+        Point(w + ScrollView::scrollbar_width(style), h.min(self.max_height))
+    }
+
+    fn prepare(&self, time: f64) { self.widget.prepare(time) }
+    fn animating(&self) -> bool { self.widget.animating() }
+    fn need_reflow(&self, style: &Style, measurer: &TextMeasurer) -> bool {
+        let Point(rw, rh) = self.widget.size_request(style, measurer);
+        let Point(aw, ah) = self.widget.size();
+        // The child is stretched to fill the view, so compare against what `set_size` assigns.
+        self.widget.need_reflow(style, measurer) || rw > aw || rh.max(self.size().1) != ah
+    }
+
+    fn children<'a>(&'a self) -> Vec<&'a Widget> { vec![&*self.widget] }
+
+    fn render(&self, nvg: &nanovg::Context, style: &Style) {
+        let state = self.state.borrow();
+        let Point(w, h) = state.size;
+
+        nvg.save();
+        nvg.intersect_scissor(0., 0., w, h);
+        nvg.translate(0., -state.offset);
+        self.widget.render(nvg, style);
+        nvg.restore();
+
+        let Point(_, child_h) = self.widget.size();
+        if child_h > h {
+            let bar_w = ScrollView::scrollbar_width(style);
+            let (bar_h, bar_y) = (h * h / child_h, h * state.offset / child_h);
+            nvg.begin_path();
+            nvg.rounded_rect(w - bar_w, bar_y, bar_w, bar_h, bar_w / 2.);
+            nvg.fill_color(style.passive_color);
+            nvg.fill();
+        }
+    }
+
+    fn project(&self, point: Point) -> Option<(&Widget, Point)> {
+        let offset = self.state.borrow().offset;
+        match self.widget.project(point + Point(0., offset)) {
+            Some((widget, point)) => Some((widget, point)),
+            // Let the gaps between widgets scroll the view.
+            None => Some((self, point))
+        }
+    }
+
+    fn mouse_scroll(&self, offset: Point) {
+        let max_offset = self.max_offset();
+        let mut state = self.state.borrow_mut();
+        let offset = state.offset - offset.1 * state.scroll_step;
+        state.offset = offset.max(0.).min(max_offset)
+    }
+}
+
+// Frame
+
+pub struct Frame<'nvg> {
+    widget: Box<Widget + 'nvg>,
+    title: String,
+    state: RefCell<FrameState>,
+}
+
+struct FrameState {
+    size: Point,
+    position: Point,
+    // Where the title bar was grabbed, and the position of the frame at that moment.
+    moving: Option<(Point, Point)>,
+    // Where the grip was grabbed, and the size of the frame at that moment.
+    resizing: Option<(Point, Point)>,
+    // Size set by dragging the grip; the frame is never smaller than its contents.
+    user_size: Point,
+    collapsed: bool,
+    reflow: bool,
+    // Metrics as of the last layout, which input is matched against.
+    content_origin: Point,
+    title_rect: Rect,
+    grip_rect: Rect,
+    arrow_rect: Rect,
+}
+
+impl<'nvg> Frame<'nvg> {
+    pub fn new(widget: Box<Widget + 'nvg>) -> Frame<'nvg> {
+        Frame {
+            widget: widget,
+            title: String::new(),
+            state: RefCell::new(FrameState {
+                size: Point(0., 0.),
+                position: Point(0., 0.),
+                moving: None,
+                resizing: None,
+                user_size: Point(0., 0.),
+                collapsed: false,
+                reflow: false,
+                content_origin: Point(0., 0.),
+                title_rect: Rect(Point(0., 0.), Point(0., 0.)),
+                grip_rect: Rect(Point(0., 0.), Point(0., 0.)),
+                arrow_rect: Rect(Point(0., 0.), Point(0., 0.)),
+            })
+        }
+    }
+
+    pub fn position(&self) -> Point {
+        self.state.borrow().position
+    }
+
+    pub fn set_position(&mut self, point: Point) {
+        self.state.borrow_mut().position = point
+    }
+
+    /// Moves the frame so that enough of its title bar to grab it is within `viewport`.
+    pub fn clamp_position(&self, viewport: Point, style: &Style) {
+        let margin = Frame::title_height(style) * 2.;
+        let Point(width, _) = self.size();
+        let Rect(_, Point(_, title_height)) = self.title_rect();
+
+        let mut state = self.state.borrow_mut();
+        let Point(x, y) = state.position;
+        state.position = Point(x.min(viewport.0 - margin).max(margin - width),
+                               y.min(viewport.1 - title_height).max(0.))
+    }
+
+    pub fn set_title(&mut self, title: String) {
+        self.title = title;
+        self.state.borrow_mut().reflow = true
+    }
+
+    pub fn collapsed(&self) -> bool {
+        self.state.borrow().collapsed
+    }
+
+    pub fn set_collapsed(&self, collapsed: bool) {
+        let mut state = self.state.borrow_mut();
+        state.collapsed = collapsed;
+        state.reflow = true
+    }
+
+    fn title_height(style: &Style) -> f32 { style.font_size }
+    fn grip_size(style: &Style) -> f32 { style.font_size / 2. }
+    fn arrow_size(style: &Style) -> f32 { style.font_size / 2. }
+
+    fn content_offset(style: &Style) -> Point {
+        Point(style.frame_corner_size, style.frame_corner_size)
+    }
+
+    // Top left corner of the contents, relative to the frame.
+    fn content_origin(style: &Style) -> Point {
+        Frame::content_offset(style) + Point(0., Frame::title_height(style))
+    }
+
+    fn title_rect(&self) -> Rect { self.state.borrow().title_rect }
+    fn grip_rect(&self) -> Rect { self.state.borrow().grip_rect }
+    fn arrow_rect(&self) -> Rect { self.state.borrow().arrow_rect }
+}
+
+impl<'nvg> Widget for Frame<'nvg> {
+    fn size(&self) -> Point { self.state.borrow().size }
+
+    fn set_size(&self, size: Point, style: &Style, measurer: &TextMeasurer) {
+        let Point(w, h) = size;
+        let Point(offset_x, offset_y) = Frame::content_offset(style);
+        let (title_height, grip_size) = (Frame::title_height(style), Frame::grip_size(style));
+
+        let mut state = self.state.borrow_mut();
+        state.size = size;
+        state.reflow = false;
+        state.content_origin = Frame::content_origin(style);
+        state.title_rect = Rect(Point(0., 0.), Point(w, state.content_origin.1));
+        state.grip_rect = Rect(Point(w - grip_size, h - grip_size), Point(grip_size, grip_size));
+        state.arrow_rect = Rect(Point(w - offset_x - title_height, offset_y),
+                                Point(title_height, title_height));
+        if !state.collapsed {
+            self.widget.set_size(size - state.content_origin - Frame::content_offset(style),
+                                 style, measurer)
+        }
+    }
+
+    fn size_request(&self, style: &Style, measurer: &TextMeasurer) -> Point {
+        let title_w = measurer.text_size(style, &self.title).0 + Frame::title_height(style) +
+                      Frame::content_offset(style).0 * 3.;
+
+        let Point(w, h) = self.widget.size_request(style, measurer) +
+                          Frame::content_origin(style) + Frame::content_offset(style);
+        let state = self.state.borrow();
+        let Point(user_w, user_h) = state.user_size;
+        let w = w.max(title_w).max(user_w);
+        if state.collapsed {
+            // Keep the width, so that the frame does not jump around when toggled.
+            Point(w, h - self.widget.size_request(style, measurer).1)
+        } else {
+            Point(w, h.max(user_h))
+        }
+    }
+
+    fn prepare(&self, time: f64) { self.widget.prepare(time) }
+
+    fn animating(&self) -> bool {
+        !self.state.borrow().collapsed && self.widget.animating()
+    }
+
+    fn need_reflow(&self, style: &Style, measurer: &TextMeasurer) -> bool {
+        let state = self.state.borrow();
+        state.reflow || (!state.collapsed && self.widget.need_reflow(style, measurer))
+    }
+
+    fn children<'a>(&'a self) -> Vec<&'a Widget> {
+        if self.state.borrow().collapsed { vec![] } else { vec![&*self.widget] }
+    }
+
+    fn render(&self, nvg: &nanovg::Context, style: &Style) {
         let state = self.state.borrow();
         let (Point(x, y), Point(w, h)) = (state.position, state.size);
-        let style = Style::get();
 
-        self.nvg.begin_path();
-        self.nvg.rounded_rect(x, y, w, h, style.frame_corner_size);
-        self.nvg.stroke_width(style.line_size);
-        self.nvg.stroke_color(style.passive_color);
-        self.nvg.stroke();
-        self.nvg.fill_color(style.background_color);
-        self.nvg.fill();
+        nvg.begin_path();
+        nvg.rounded_rect(x, y, w, h, style.frame_corner_size);
+        nvg.stroke_width(style.line_size);
+        nvg.stroke_color(style.passive_color);
+        nvg.stroke();
+        nvg.fill_color(style.background_color);
+        nvg.fill();
+
+        let Point(offset_x, offset_y) = Frame::content_offset(style);
+        nvg.font_face(&style.font_face);
+        nvg.font_size(style.font_size);
+        nvg.text_align(nanovg::LEFT | nanovg::MIDDLE);
+        nvg.fill_color(style.passive_color);
+        nvg.text(x + offset_x, y + offset_y + Frame::title_height(style) / 2., &self.title);
+
+        let arrow_size = Frame::arrow_size(style);
+        let Rect(Point(arrow_x, arrow_y), Point(arrow_w, arrow_h)) = state.arrow_rect;
+        let (arrow_x, arrow_y) = (x + arrow_x + (arrow_w - arrow_size) / 2.,
+                                  y + arrow_y + (arrow_h - arrow_size) / 2.);
+        nvg.fill_color(style.active_color);
+        nvg.begin_path();
+        if state.collapsed {
+            nvg.move_to(arrow_x, arrow_y);
+            nvg.line_to(arrow_x + arrow_size, arrow_y + arrow_size / 2.);
+            nvg.line_to(arrow_x, arrow_y + arrow_size);
+        } else {
+            nvg.move_to(arrow_x, arrow_y);
+            nvg.line_to(arrow_x + arrow_size / 2., arrow_y + arrow_size);
+            nvg.line_to(arrow_x + arrow_size, arrow_y);
+        }
+        nvg.close_path();
+        nvg.fill();
+
+        if state.collapsed { return }
 
-        self.nvg.save();
-        self.nvg.translate(x + Style::get().frame_corner_size,
-                           y + Style::get().frame_corner_size);
-        self.nvg.scissor(0., 0., w, h);
-        self.widget.render();
-        self.nvg.restore();
+        let Rect(Point(grip_x, grip_y), Point(grip_w, grip_h)) = state.grip_rect;
+        nvg.begin_path();
+        for step in 1..4 {
+            let inset = grip_w * step as f32 / 4.;
+            nvg.move_to(x + grip_x + inset, y + grip_y + grip_h);
+            nvg.line_to(x + grip_x + grip_w, y + grip_y + inset);
+        }
+        nvg.stroke_width(style.line_size / 4.);
+        nvg.stroke_color(style.passive_color);
+        nvg.stroke();
+
+        let Point(content_x, content_y) = state.content_origin;
+        nvg.save();
+        nvg.translate(x + content_x, y + content_y);
+        nvg.scissor(0., 0., w, h);
+        self.widget.render(nvg, style);
+        nvg.restore();
     }
 
     fn project(&self, point: Point) -> Option<(&Widget, Point)> {
         let state = self.state.borrow();
-        let origin = state.position + Frame::content_offset();
+        let origin = state.position + state.content_origin;
+        let Rect(grip_origin, grip_size) = state.grip_rect;
 
-        if Rect(origin, self.widget.size()).contains(point) {
+        if !state.collapsed && Rect(state.position + grip_origin, grip_size).contains(point) {
+            // The grip is on top of the contents.
+            Some((self, point - state.position))
+        } else if !state.collapsed && Rect(origin, self.widget.size()).contains(point) {
             match self.widget.project(point - origin) {
                 Some(result) => Some(result),
-                None => Some((self, Point(0., 0.)))
+                None => Some((self, point - state.position))
             }
-        } else if Rect(state.position, self.size()).contains(point) {
-            Some((self, Point(0., 0.)))
+        } else if Rect(state.position, state.size).contains(point) {
+            Some((self, point - state.position))
         } else {
             None
         }
     }
 
     fn mouse_down(&self, point: Point) {
-        let mut state = self.state.borrow_mut();
-        state.moving = Some(point - state.position)
+        if !self.collapsed() && self.grip_rect().contains(point) {
+            let mut state = self.state.borrow_mut();
+            state.resizing = Some((point, state.size))
+        } else if self.arrow_rect().contains(point) {
+            let collapsed = self.collapsed();
+            self.set_collapsed(!collapsed)
+        } else if self.title_rect().contains(point) {
+            let mut state = self.state.borrow_mut();
+            state.moving = Some((point, state.position))
+        }
     }
 
     fn mouse_up(&self, _point: Point) {
-        self.state.borrow_mut().moving = None
+        let mut state = self.state.borrow_mut();
+        state.moving = None;
+        state.resizing = None
     }
 
     fn mouse_move(&self, point: Point) {
+        // The offset used to compute `point` stays the same while the mouse is captured,
+        // so it has to be compared with where the frame was grabbed.
         let mut state = self.state.borrow_mut();
         match state.moving {
-            Some(origin) => state.position = (point - origin).round(),
+            Some((grab, position)) => state.position = (position + point - grab).round(),
+            None => ()
+        }
+        match state.resizing {
+            Some((grab, size)) => {
+                // `size_request` keeps the frame from shrinking below its contents.
+                state.user_size = (size + point - grab).round();
+                state.reflow = true
+            }
             None => ()
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use ui::{Point, Rect, Alignment};
+    use super::*;
+
+    // Every character is 10 by 20 pixels, so that layout can be checked without GL.
+    struct FixedMeasurer;
+
+    impl TextMeasurer for FixedMeasurer {
+        fn text_size(&self, _style: &Style, text: &str) -> Point {
+            Point(text.chars().count() as f32 * 10., 20.)
+        }
+
+        fn text_box_size(&self, _style: &Style, width: f32, text: &str) -> Point {
+            let per_line = ((width / 10.) as usize).max(1);
+            let count = text.chars().count();
+            let lines = (count + per_line - 1) / per_line;
+            Point(count.min(per_line) as f32 * 10., lines as f32 * 20.)
+        }
+    }
+
+    fn sizes(layout: &BoxLayout) -> Vec<Point> {
+        layout.iter().map(|child| child.size()).collect()
+    }
+
+    #[test]
+    fn label_is_measured() {
+        let style = Style::new(Theme::Dark, 1.);
+        let label = Label::new();
+        label.text().set("abc".to_string());
+        assert_eq!(label.size_request(&style, &FixedMeasurer), Point(30., 20.));
+        label.set_wrap(Some(20.));
+        assert_eq!(label.size_request(&style, &FixedMeasurer), Point(20., 40.));
+    }
+
+    #[test]
+    fn box_layout_requests_children_and_overhead() {
+        let style = Style::new(Theme::Dark, 1.);
+        let mut layout = BoxLayout::horz();
+        layout.set_spacing(2.);
+        layout.set_padding(Point(1., 3.));
+        layout.add(Box::new(Spacer::new(Point(10., 5.))));
+        layout.add(Box::new(Spacer::new(Point(30., 8.))));
+        assert_eq!(layout.size_request(&style, &FixedMeasurer), Point(44., 14.));
+    }
+
+    #[test]
+    fn box_layout_splits_extra_space_by_request() {
+        let style = Style::new(Theme::Dark, 1.);
+        let mut layout = BoxLayout::horz();
+        layout.set_spacing(2.);
+        layout.set_padding(Point(1., 3.));
+        layout.add(Box::new(Spacer::new(Point(10., 5.))));
+        layout.add(Box::new(Spacer::new(Point(30., 8.))));
+        layout.set_size(Point(84., 14.), &style, &FixedMeasurer);
+        assert_eq!(sizes(&layout), vec![Point(20., 8.), Point(60., 8.)]);
+    }
+
+    #[test]
+    fn box_layout_splits_extra_space_by_weight() {
+        let style = Style::new(Theme::Dark, 1.);
+        let mut layout = BoxLayout::vert();
+        layout.add_weighted(Box::new(Spacer::new(Point(5., 10.))), 0.);
+        layout.add_weighted(Box::new(Spacer::new(Point(5., 30.))), 1.);
+        layout.set_size(Point(5., 80.), &style, &FixedMeasurer);
+        assert_eq!(sizes(&layout), vec![Point(5., 10.), Point(5., 70.)]);
+    }
+
+    #[test]
+    fn box_layout_aligns_across() {
+        let style = Style::new(Theme::Dark, 1.);
+        let mut layout = BoxLayout::horz();
+        layout.set_alignment(Alignment::Center);
+        layout.add(Box::new(Spacer::new(Point(10., 4.))));
+        layout.set_size(Point(10., 10.), &style, &FixedMeasurer);
+        assert_eq!(sizes(&layout), vec![Point(10., 4.)]);
+        assert_eq!(layout.child_origins(), vec![Point(0., 3.)]);
+    }
+
+    #[test]
+    fn frame_insets_contents() {
+        let style = Style::new(Theme::Dark, 1.);
+        let frame = Frame::new(Box::new(Button::new("ok".to_string())));
+        let size = frame.size_request(&style, &FixedMeasurer);
+        frame.set_size(size, &style, &FixedMeasurer);
+        assert_eq!(frame.widget.size(),
+                   size - Frame::content_origin(&style) - Frame::content_offset(&style));
+
+        let origin = frame.position() + Frame::content_origin(&style);
+        let (widget, point) = frame.project(origin + Point(1., 1.)).unwrap();
+        assert!(widget.is(&*frame.widget));
+        assert_eq!(point, Point(1., 1.));
+    }
+
+    #[test]
+    fn scroll_view_settles_when_taller_than_contents() {
+        let style = Style::new(Theme::Dark, 1.);
+        let view = ScrollView::new(Box::new(Spacer::new(Point(10., 10.))));
+        view.set_size(Point(50., 100.), &style, &FixedMeasurer);
+        assert!(!view.need_reflow(&style, &FixedMeasurer));
+    }
+
+    #[test]
+    fn frame_settles_after_resizing() {
+        let style = Style::new(Theme::Dark, 1.);
+        let scroll = ScrollView::new(Box::new(Spacer::new(Point(10., 10.))));
+        let frame = Frame::new(Box::new(scroll));
+        let initial = frame.size_request(&style, &FixedMeasurer);
+        frame.set_size(initial, &style, &FixedMeasurer);
+        assert!(!frame.need_reflow(&style, &FixedMeasurer));
+
+        let Rect(grip_origin, grip_size) = frame.grip_rect();
+        let grab = grip_origin + grip_size / 2.;
+        frame.mouse_down(grab);
+        frame.mouse_move(grab + Point(0., 50.));
+        frame.mouse_up(grab + Point(0., 50.));
+        assert!(frame.need_reflow(&style, &FixedMeasurer));
+
+        // The contents are now shorter than the frame; laying it out once has to be enough,
+        // or an idle window is drawn on every pass.
+        let size = frame.size_request(&style, &FixedMeasurer);
+        assert_eq!(size.1, initial.1 + 50.);
+        frame.set_size(size, &style, &FixedMeasurer);
+        assert!(!frame.need_reflow(&style, &FixedMeasurer));
+    }
+
+    #[test]
+    fn frame_is_as_wide_as_its_title() {
+        let style = Style::new(Theme::Dark, 1.);
+        let mut frame = Frame::new(Box::new(Spacer::new(Point(10., 10.))));
+        frame.set_title("a long enough title".to_string());
+        assert!(frame.size_request(&style, &FixedMeasurer).0 > 190.);
+    }
+
+    #[test]
+    fn logarithmic_slider_position_round_trips() {
+        let position = SliderPosition { current: 0., minimum: 1., maximum: 1000., step: 1. };
+        for &value in &[1., 10., 50., 999., 1000.] {
+            let norm_value = position.change(value).normalized(SliderScale::Logarithmic);
+            let current = position.denormalized(SliderScale::Logarithmic, norm_value).current;
+            assert!((current - value).abs() < value * 1e-4, "{} became {}", value, current);
+        }
+        assert!((position.change(10.).normalized(SliderScale::Logarithmic) - 1. / 3.).abs() <
+                1e-4);
+    }
+
+    #[test]
+    fn logarithmic_slider_starts_at_step_if_minimum_is_zero() {
+        let position = SliderPosition { current: 0., minimum: 0., maximum: 100., step: 0.1 };
+        assert_eq!(position.normalized(SliderScale::Logarithmic), 0.);
+        assert_eq!(position.denormalized(SliderScale::Logarithmic, 0.).current, 0.1);
+        let current = position.denormalized(SliderScale::Logarithmic, 1.).current;
+        assert!((current - 100.).abs() < 1e-3);
+    }
+
+    #[test]
+    fn slider_value_is_copied_without_unit() {
+        let slider = Slider::new(SliderPosition {
+            current: 12.5, minimum: 0., maximum: 100., step: 0.1
+        }).with_unit("ms".to_string());
+        assert_eq!(slider.copy_text(), Some("12.5".to_string()));
+    }
+}